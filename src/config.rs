@@ -1,22 +1,423 @@
-use std::f32::consts::PI;
+use std::time::SystemTime;
 
-pub const RADIUS: f32 = 10.0;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::{ RapierConfiguration, TimestepMode };
+use serde::{ Deserialize, Serialize };
 
-pub const wall_half_x: f32 = 10.0;
-pub const wall_half_y: f32 = 500.0;
-pub const floor_half_x: f32 = 400.0;
-pub const floor_half_y: f32 = 10.0;
+/// Read at startup when no --config is given, if it exists
+pub const DEFAULT_CONFIG_PATH: &str = "assets/yuki.ron";
+/// Seconds between checks of the config file for changes
+pub const CONFIG_POLL_SECS: f32 = 1.0;
 
 pub const VISIBLE_Z: f32 = -1.0;
 pub const CHILD_VISIBLE_Z: f32 = 0.5;
+/// Thickness of the ring drawn around each Snow body, in pixels
+pub const OUTLINE_WIDTH: f32 = 3.0;
+pub const DANGER_PULSE_SPEED: f32 = 8.0;
 
+/// Container and body settings, owned by GamePlugin
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct GameConfig {
+    /// Pixels per unit of Body::radius
+    pub radius: f32,
+    pub wall_half_x: f32,
+    pub wall_half_y: f32,
+    pub floor_half_x: f32,
+    pub floor_half_y: f32,
+    pub lin_damping: f32,
+    pub ang_damping: f32,
+    /// Settled bodies poking above this height end the run
+    pub danger_line_y: f32,
+    /// Seconds a body may rest above the danger line before the game is over
+    pub danger_grace: f32,
+    /// Bodies slower than this (pixels per second) count as settled
+    pub settled_speed: f32,
+}
 
-pub const CAMERA_SPEED_SCALAR: f32 = 10.0;
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            radius: 10.0,
+            wall_half_x: 10.0,
+            wall_half_y: 500.0,
+            floor_half_x: 400.0,
+            floor_half_y: 10.0,
+            lin_damping: 0.0,
+            ang_damping: 0.0,
+            danger_line_y: 350.0,
+            danger_grace: 3.0,
+            settled_speed: 20.0,
+        }
+    }
+}
 
-pub const SPEED_SCALAR: f32 = 5.0;
-pub const DEFAULT_LIN_DAMPING: f32 = 0.0;
-pub const DEFAULT_ANG_DAMPING: f32 = 0.0;
+impl GameConfig {
+    /// Converts a Body::radius into pixels
+    pub fn pixels(&self, radius: f32) -> f32 {
+        radius * self.radius
+    }
 
-pub const PIXELS_PER_METER: f32 = 100.0;
-pub const DEFAULT_CAM_SCALE: f32 = 1.5;
-pub const GRAVITY: bool = true;
+    /// Distance from the center to the inner face of either wall
+    pub fn inner_half_x(&self) -> f32 {
+        self.floor_half_x - self.wall_half_x
+    }
+
+    /// Half size of the box around the walls and floor, which sits on the origin
+    pub fn container_half_size(&self) -> Vec2 {
+        Vec2::new(self.floor_half_x + self.wall_half_x, self.wall_half_y + self.floor_half_y)
+    }
+}
+
+/// Merging, scoring and dropping rules, owned by LogicPlugin
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct LogicConfig {
+    /// RON tier table, relative to the working directory
+    pub tiers_path: String,
+    /// Seconds between merges for them to count towards the same chain
+    pub chain_window: f32,
+    /// Extra score multiplier per merge in a chain after the first
+    pub chain_bonus: f32,
+    /// Height the held piece waits at before it is dropped
+    pub drop_y: f32,
+    /// Seconds between drops
+    pub drop_cooldown: f32,
+    /// How fast A/D move the held piece, in pixels per second
+    pub drop_aim_speed: f32,
+    /// Number of upcoming pieces shown after the held one
+    pub preview_len: usize,
+    /// Reach of the burst when two max-tier bodies meet
+    pub burst_radius: f32,
+    /// Speed given to a body at the center of a burst, in pixels per second
+    pub burst_speed: f32,
+    /// How many GameConfig::radius sized steps a merged body may be moved to get clear of the walls
+    pub spawn_nudge_steps: usize,
+    /// Seeds GameRng on startup, a random seed is picked (and logged) when left out
+    pub seed: Option<u64>,
+    /// Where F5 saves the board and F9 loads it from
+    pub save_path: String,
+    /// How many drops back Z can undo
+    pub undo_depth: usize,
+}
+
+impl Default for LogicConfig {
+    fn default() -> Self {
+        Self {
+            tiers_path: "assets/tiers.ron".to_string(),
+            chain_window: 1.5,
+            chain_bonus: 0.5,
+            drop_y: 450.0,
+            drop_cooldown: 0.5,
+            drop_aim_speed: 400.0,
+            preview_len: 3,
+            burst_radius: 400.0,
+            burst_speed: 800.0,
+            spawn_nudge_steps: 50,
+            seed: None,
+            save_path: "quicksave.ron".to_string(),
+            undo_depth: 10,
+        }
+    }
+}
+
+/// Physics and camera setup, owned by SetupPlugin (or HeadlessPlugin)
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct SetupConfig {
+    pub pixels_per_meter: f32,
+    /// In pixels per second squared
+    pub gravity: Vec2,
+    /// Physics step in seconds, used when headless or with fixed_timestep
+    pub fixed_dt: f32,
+    /// Step physics by fixed_dt instead of by the frame time, so runs can be reproduced. Steps happen
+    /// at most once a frame, so the debug time scale and replay speed can only slow it down.
+    pub fixed_timestep: bool,
+    pub camera_scale: f32,
+}
+
+impl Default for SetupConfig {
+    fn default() -> Self {
+        Self {
+            pixels_per_meter: 100.0,
+            gravity: Vec2::Y * -9.81 * 100.0,
+            fixed_dt: 1.0 / 60.0,
+            fixed_timestep: false,
+            camera_scale: 1.5,
+        }
+    }
+}
+
+/// Player input tuning, owned by ControlsPlugin
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ControlsConfig {
+    pub camera_speed: f32,
+    /// Scales forces when driving a body by hand
+    pub speed_scalar: f32,
+    /// Spring stiffness pulling a dragged body towards the cursor
+    pub grab_stiffness: f32,
+    /// How quickly the camera catches up with a followed body, per second
+    pub follow_speed: f32,
+    /// The followed body can move this far from the center of the screen before the camera moves, in pixels
+    pub follow_deadzone: f32,
+    /// Zoom so the followed body stays the same size on screen
+    pub follow_zoom_fit: bool,
+    /// Closest the camera can zoom in, as an orthographic scale
+    pub min_zoom: f32,
+    /// Furthest the camera can zoom out, as an orthographic scale
+    pub max_zoom: f32,
+    /// Scale change per notch of the mouse wheel
+    pub wheel_zoom_step: f32,
+    /// Fit the whole container in view whenever the window is resized
+    pub auto_fit: bool,
+    /// Keep the camera fitted on the container while playing, ignoring pans and zooms
+    pub lock_camera: bool,
+    /// RON key, mouse and gamepad bindings, relative to the working directory. Defaults are used without one.
+    pub keymap_path: String,
+}
+
+impl Default for ControlsConfig {
+    fn default() -> Self {
+        Self {
+            camera_speed: 10.0,
+            speed_scalar: 5.0,
+            grab_stiffness: 200.0,
+            follow_speed: 5.0,
+            follow_deadzone: 0.0,
+            follow_zoom_fit: false,
+            min_zoom: 0.1,
+            max_zoom: 10.0,
+            wheel_zoom_step: 0.1,
+            auto_fit: true,
+            lock_camera: false,
+            keymap_path: "assets/keymap.ron".to_string(),
+        }
+    }
+}
+
+impl ControlsConfig {
+    pub fn clamp_zoom(&self, scale: f32) -> f32 {
+        scale.clamp(self.min_zoom, self.max_zoom)
+    }
+}
+
+/// Every plugin's settings in one file, see assets/yuki.ron.
+/// Loaded at startup and copied into the per-plugin config resources whenever it changes.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct YukiConfig {
+    pub game: GameConfig,
+    pub logic: LogicConfig,
+    pub setup: SetupConfig,
+    pub controls: ControlsConfig,
+}
+
+impl YukiConfig {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs
+            ::read_to_string(path)
+            .map_err(|e| format!("Couldn't read config {}: {}", path, e))?;
+        ron::from_str(&text).map_err(|e| format!("Couldn't parse config {}: {}", path, e))
+    }
+
+    /// Applies overrides like `game.radius=12.0`, where the value is written in RON
+    pub fn with_overrides(self, overrides: &[String]) -> Result<Self, String> {
+        if overrides.is_empty() {
+            return Ok(self);
+        }
+        let text = ron::to_string(&self).map_err(|e| e.to_string())?;
+        let mut value: ron::Value = ron::from_str(&text).map_err(|e| e.to_string())?;
+
+        for o in overrides {
+            let (key, raw) = o.split_once('=').ok_or(format!("Override {} should be key=value", o))?;
+            let new: ron::Value = ron
+                ::from_str(raw)
+                .map_err(|e| format!("Couldn't parse override {}: {}", o, e))?;
+
+            let mut target = &mut value;
+            for part in key.split('.') {
+                let ron::Value::Map(map) = target else {
+                    return Err(format!("Override {} goes past a setting", o));
+                };
+                target = map
+                    .iter_mut()
+                    .find(|(k, _)| **k == ron::Value::String(part.to_string()))
+                    .map(|(_, v)| v)
+                    .ok_or(format!("Unknown setting {} in {}", part, o))?;
+            }
+            *target = new;
+        }
+
+        value.into_rust().map_err(|e| format!("Overrides don't fit the config: {}", e))
+    }
+}
+
+/// Where the config came from, so a reload applies the same overrides
+#[derive(Resource, Clone, Debug, Default)]
+pub struct ConfigSource {
+    pub path: Option<String>,
+    pub overrides: Vec<String>,
+}
+
+impl ConfigSource {
+    /// Reads `--config <path>`, `--seed <n>` and any number of `--set key=value` from the command line
+    pub fn from_args(args: &[String]) -> Self {
+        let mut source = ConfigSource::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => {
+                    source.path = args.next().cloned();
+                }
+                "--set" => {
+                    source.overrides.extend(args.next().cloned());
+                }
+                "--seed" => {
+                    source.overrides.extend(args.next().map(|seed| format!("logic.seed=Some({})", seed)));
+                }
+                _ => {}
+            }
+        }
+        if source.path.is_none() && std::path::Path::new(DEFAULT_CONFIG_PATH).exists() {
+            source.path = Some(DEFAULT_CONFIG_PATH.to_string());
+        }
+        source
+    }
+
+    pub fn load(&self) -> Result<YukiConfig, String> {
+        let config = match &self.path {
+            Some(path) => YukiConfig::load(path)?,
+            None => YukiConfig::default(),
+        };
+        config.with_overrides(&self.overrides)
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        let path = self.path.as_ref()?;
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+}
+
+/// Keeps YukiConfig in sync with its file and hands changes on to the plugins, with gravity and fixed_dt going
+/// straight on to rapier. Container size, body radius, pixels_per_meter, fixed_timestep, the seed,
+/// the tier table path and the keymap path only take effect on startup.
+pub struct ConfigPlugin {
+    pub source: ConfigSource,
+    pub config: YukiConfig,
+}
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.source.clone())
+            .insert_resource(self.config.clone())
+            .add_systems(PreUpdate, (hot_reload, sync_sections.after(hot_reload)))
+            .add_systems(
+                Update,
+                apply_physics.run_if(
+                    resource_exists::<SetupConfig>().and_then(resource_changed::<SetupConfig>())
+                )
+            );
+    }
+}
+
+fn hot_reload(
+    source: Res<ConfigSource>,
+    mut config: ResMut<YukiConfig>,
+    mut last_modified: Local<Option<SystemTime>>,
+    mut since_check: Local<f32>,
+    time: Res<Time>
+) {
+    *since_check += time.delta_seconds();
+    if *since_check < CONFIG_POLL_SECS {
+        return;
+    }
+    *since_check = 0.0;
+
+    let modified = source.modified();
+    if last_modified.is_none() {
+        *last_modified = modified;
+        return;
+    }
+    if modified == *last_modified {
+        return;
+    }
+    *last_modified = modified;
+
+    match source.load() {
+        Ok(new) => {
+            if new != *config {
+                info!("Reloaded config from {:?}", source.path);
+                *config = new;
+            }
+        }
+        Err(e) => warn!("Keeping the old config, {}", e),
+    }
+}
+
+fn sync_sections(
+    config: Res<YukiConfig>,
+    game: Option<ResMut<GameConfig>>,
+    logic: Option<ResMut<LogicConfig>>,
+    setup: Option<ResMut<SetupConfig>>,
+    controls: Option<ResMut<ControlsConfig>>
+) {
+    if !config.is_changed() {
+        return;
+    }
+    if let Some(mut game) = game {
+        // meshes and walls are built once, so their sizes stay as they started
+        let new = GameConfig {
+            radius: game.radius,
+            wall_half_x: game.wall_half_x,
+            wall_half_y: game.wall_half_y,
+            floor_half_x: game.floor_half_x,
+            floor_half_y: game.floor_half_y,
+            ..config.game.clone()
+        };
+        game.set_if_neq(new);
+    }
+    if let Some(mut logic) = logic {
+        logic.set_if_neq(config.logic.clone());
+    }
+    if let Some(mut setup) = setup {
+        setup.set_if_neq(config.setup.clone());
+    }
+    if let Some(mut controls) = controls {
+        controls.set_if_neq(config.controls.clone());
+    }
+}
+
+fn apply_physics(setup: Res<SetupConfig>, mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.gravity = setup.gravity;
+    if let TimestepMode::Fixed { dt, .. } = &mut rapier_config.timestep_mode {
+        *dt = setup.fixed_dt;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overridden(overrides: &[&str]) -> Result<YukiConfig, String> {
+        let overrides = overrides
+            .iter()
+            .map(|o| o.to_string())
+            .collect::<Vec<_>>();
+        YukiConfig::default().with_overrides(&overrides)
+    }
+
+    #[test]
+    fn overrides_reach_nested_and_optional_settings() {
+        let config = overridden(&["game.radius=12.0", "logic.seed=Some(7)"]).expect("Overrides apply");
+        assert_eq!(config.game.radius, 12.0);
+        assert_eq!(config.logic.seed, Some(7));
+        assert_eq!(config.setup, SetupConfig::default());
+    }
+
+    #[test]
+    fn unknown_override_is_an_error() {
+        assert!(overridden(&["game.radius_typo=12.0"]).is_err());
+        assert!(overridden(&["game.radius.x=1.0"]).is_err());
+    }
+}
@@ -1,23 +1,80 @@
 use std::collections::BTreeSet;
 
-use bevy::{ time::Stopwatch };
+use bevy::{
+    ecs::entity::Entities,
+    input::mouse::{ MouseMotion, MouseScrollUnit, MouseWheel },
+    time::Stopwatch,
+    transform::TransformSystem,
+    window::WindowResized,
+};
 use rand::seq::IteratorRandom;
-use crate::logic::hud::{ActiveControl, SelectedPos};
+use bevy_egui::{ egui, EguiContexts };
+use bevy_rapier2d::rapier::prelude::{ JointAxesMask, JointAxis };
+use crate::logic::{
+    hud::{ActiveControl, CameraFollows},
+    drop::Dropper,
+    body::Body,
+    merge::{despawn_claimed, Claimed},
+    save::{SaveGame, LoadGame},
+    undo::UndoDrop,
+};
 
 use crate::{
     prelude::*,
-    setup::MoveCamera,
+    setup::{ MoveCamera, InspectorSelection },
+    replay::{ replaying, recording, Recorder },
+    game::{ GameState, snow::Snow },
+    input::{ update_actions, ActionState, GameAction, PointerCaptured },
 };
 
-pub struct ControlsPlugin;
+/// How far in screen pixels the mouse has to move with the button down to box select rather than click
+pub const SELECT_DRAG_PIXELS: f32 = 4.0;
+/// How much of each frame's cursor velocity goes into the throw velocity
+pub const GRAB_VEL_SMOOTHING: f32 = 0.3;
+/// With follow_zoom_fit, the smaller side of the window fits this many of the followed body
+pub const FOLLOW_FIT_BODIES: f32 = 8.0;
+/// Scale the arrow keys' force, torque and kick by these on top of ControlsConfig::speed_scalar
+pub const DRIVE_FORCE: f32 = 10.0;
+pub const DRIVE_TORQUE: f32 = 50.0;
+pub const DRIVE_IMPULSE: f32 = 4.0;
+/// Room left around the container when fitting it in the window, 1.0 is edge to edge
+pub const CONTAINER_FIT_MARGIN: f32 = 1.1;
+/// Touchpads scroll in pixels, this many make up one wheel notch
+pub const WHEEL_PIXELS_PER_LINE: f32 = 100.0;
+
+#[derive(Default)]
+pub struct ControlsPlugin {
+    pub config: ControlsConfig,
+}
 
 impl Plugin for ControlsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
+        app.insert_resource(self.config.clone()).add_systems(Update, (
             mouse_selection,
             // random_active_control,
-            camera_commands,
-        )).init_resource::<GUISelect>();
+            (camera_commands, wheel_zoom, middle_pan).run_if(not(camera_locked)),
+            lock_on_container.run_if(camera_locked),
+            quick_save.run_if(not(replaying)),
+            // replays don't carry grabs or driving, so they're off while recording
+            drag_body.run_if(not(replaying)).run_if(not(recording)),
+            drive_active_control.run_if(not(replaying)).run_if(not(recording)),
+        ).run_if(in_state(GameState::Playing)))
+            .add_systems(Update, (highlight_selection, grab_window, fit_on_resize))
+            .add_systems(PreUpdate, track_pointer.before(update_actions))
+            // sees Claimed before the merged body is despawned, and moves the camera before transforms propagate
+            .add_systems(
+                PostUpdate,
+                (
+                    follow_camera
+                        .run_if(not(camera_locked))
+                        .before(despawn_claimed)
+                        .before(TransformSystem::TransformPropagate),
+                    hand_off_control.before(despawn_claimed),
+                )
+            )
+            .init_resource::<GUISelect>()
+            .init_resource::<InspectorSelection>()
+            .init_resource::<Grab>();
     }
 }
 
@@ -36,59 +93,226 @@ impl Plugin for ControlsPlugin {
 // }
 
 /// allows wasd movement of the camera
+#[allow(clippy::too_many_arguments)]
 fn camera_commands(
-    keys: Res<Input<KeyCode>>,
+    actions: Res<ActionState>,
     mut query: Query<(&mut Transform, &mut OrthographicProjection, With<Camera>)>,
     mut camera_moved: EventReader<MoveCamera>,
+    windows: Query<&Window>,
+    dropper: Res<Dropper>,
+    config: Res<ControlsConfig>,
+    game: Res<GameConfig>,
+    setup: Res<SetupConfig>,
 ) {
     for (mut camera_tf, mut proj, ()) in query.iter_mut() {
-        let mut x = 0.0;
-        let mut y = 0.0;
-        let mut r = 0.0;
-        let mut pressed = false;
-
         for e in camera_moved.iter() {
             camera_tf.translation = e.to.extend(camera_tf.translation.z);
+            if let Some(scale) = e.scale {
+                proj.scale = scale;
+            }
         }
 
-        // c to center
-        if keys.just_pressed(KeyCode::C) {
+        // c to center on the container
+        if actions.just_pressed(GameAction::CenterCamera) {
             camera_tf.translation = Vec2::ZERO.extend(camera_tf.translation.z);
-            proj.scale = 1.5;
+            proj.scale = windows
+                .get_single()
+                .ok()
+                .and_then(|window| container_fit(&game, window))
+                .map_or(setup.camera_scale, |fit| config.clamp_zoom(fit));
         }
 
-        if keys.pressed(KeyCode::W) {
-            y += 1.0;
-            pressed = true;
-        }
-        if keys.pressed(KeyCode::A) {
-            x -= 1.0;
-            pressed = true;
+        let y = actions.axis(GameAction::CameraPanDown, GameAction::CameraPanUp);
+        // while dropping, A/D aim the held piece instead
+        let x = if dropper.enabled {
+            0.0
+        } else {
+            actions.axis(GameAction::CameraPanLeft, GameAction::CameraPanRight)
+        };
+
+        if x != 0.0 || y != 0.0 {
+            camera_tf.translation.x += x * proj.scale * config.camera_speed;
+            camera_tf.translation.y += y * proj.scale * config.camera_speed;
         }
-        if keys.pressed(KeyCode::S) {
-            y -= 1.0;
-            pressed = true;
+
+        // = is orthographic projection in
+        if actions.pressed(GameAction::ZoomIn) {
+            proj.scale = config.clamp_zoom(proj.scale * 0.9f32.powf(actions.value(GameAction::ZoomIn)));
         }
-        if keys.pressed(KeyCode::D) {
-            x += 1.0;
-            pressed = true;
+        // space is out, unless it's releasing a drop
+        if actions.pressed(GameAction::ZoomOut) && !dropper.enabled {
+            proj.scale = config.clamp_zoom(proj.scale * 1.1f32.powf(actions.value(GameAction::ZoomOut)));
         }
+    }
+}
 
-        if pressed {
-            camera_tf.translation.x += x * proj.scale * CAMERA_SPEED_SCALAR;
-            camera_tf.translation.y += y * proj.scale * CAMERA_SPEED_SCALAR;
-        }
+/// The orthographic scale that fits the whole container in the window
+pub fn container_fit(game: &GameConfig, window: &Window) -> Option<f32> {
+    let size = Vec2::new(window.width(), window.height());
+    if size.min_element() <= 0.0 {
+        return None;
+    }
+    Some(((game.container_half_size() * 2.0 * CONTAINER_FIT_MARGIN) / size).max_element())
+}
+
+fn camera_locked(config: Res<ControlsConfig>) -> bool {
+    config.lock_camera
+}
+
+/// The mouse wheel zooms towards the cursor, keeping the world point under it in place
+fn wheel_zoom(
+    mut wheel: EventReader<MouseWheel>,
+    windows: Query<&Window>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+    mut contexts: EguiContexts,
+    config: Res<ControlsConfig>,
+) {
+    let notches: f32 = wheel
+        .iter()
+        .map(|e| match e.unit {
+            MouseScrollUnit::Line => e.y,
+            MouseScrollUnit::Pixel => e.y / WHEEL_PIXELS_PER_LINE,
+        })
+        .sum();
+    // scrolling an egui window shouldn't zoom the world behind it
+    if notches == 0.0 || contexts.ctx_mut().is_pointer_over_area() {
+        return;
+    }
+    let (Ok(window), Ok((mut camera_tf, mut proj))) = (windows.get_single(), camera.get_single_mut()) else {
+        return;
+    };
+
+    let before = cursor_world_pos(window, &camera_tf, &proj);
+    proj.scale = config.clamp_zoom(proj.scale * (1.0 - config.wheel_zoom_step).powf(notches));
+    if let (Some(before), Some(after)) = (before, cursor_world_pos(window, &camera_tf, &proj)) {
+        camera_tf.translation += (before - after).extend(0.0);
+    }
+}
+
+/// Dragging with the middle mouse button moves the world with the cursor, and stops following
+fn middle_pan(
+    actions: Res<ActionState>,
+    mut motion: EventReader<MouseMotion>,
+    mut camera: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+    mut follows: ResMut<CameraFollows>,
+) {
+    let delta: Vec2 = motion.iter().map(|e| e.delta).sum();
+    if !actions.pressed(GameAction::DragPan) || delta == Vec2::ZERO {
+        return;
+    }
+    follows.0 = None;
+    for (mut camera_tf, proj) in camera.iter_mut() {
+        // screen y points down
+        camera_tf.translation.x -= delta.x * proj.scale;
+        camera_tf.translation.y += delta.y * proj.scale;
+    }
+}
+
+/// With auto_fit, a resized window shows the whole container again
+fn fit_on_resize(
+    mut resized: EventReader<WindowResized>,
+    windows: Query<&Window>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+    config: Res<ControlsConfig>,
+    game: Res<GameConfig>,
+) {
+    if resized.iter().count() == 0 || !config.auto_fit {
+        return;
+    }
+    let Some(fit) = windows.get_single().ok().and_then(|window| container_fit(&game, window)) else {
+        return;
+    };
+    for (mut camera_tf, mut proj) in camera.iter_mut() {
+        camera_tf.translation = Vec2::ZERO.extend(camera_tf.translation.z);
+        proj.scale = config.clamp_zoom(fit);
+    }
+}
+
+/// With lock_camera, the camera stays fitted on the container for the whole run
+fn lock_on_container(
+    windows: Query<&Window>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+    config: Res<ControlsConfig>,
+    game: Res<GameConfig>,
+) {
+    let Some(fit) = windows.get_single().ok().and_then(|window| container_fit(&game, window)) else {
+        return;
+    };
+    for (mut camera_tf, mut proj) in camera.iter_mut() {
+        camera_tf.translation = Vec2::ZERO.extend(camera_tf.translation.z);
+        proj.scale = config.clamp_zoom(fit);
+    }
+}
+
+/// F5 quick-saves the board, F9 loads it back unless recording and Z undoes the last drop
+fn quick_save(
+    actions: Res<ActionState>,
+    mut save: EventWriter<SaveGame>,
+    mut load: EventWriter<LoadGame>,
+    mut undo: EventWriter<UndoDrop>,
+    config: Res<LogicConfig>,
+    recorder: Option<Res<Recorder>>,
+) {
+    if actions.just_pressed(GameAction::QuickSave) {
+        save.send(SaveGame(config.save_path.clone()));
+    }
+    if actions.just_pressed(GameAction::QuickLoad) && recorder.is_none() {
+        load.send(LoadGame(config.save_path.clone()));
+    }
+    if actions.just_pressed(GameAction::Undo) {
+        undo.send(UndoDrop);
+    }
+}
 
-        // shift is orthographic projection in
-        if keys.pressed(KeyCode::ShiftLeft) {
-            // proj must be > 0
-            if proj.scale > 0.01 {
-                proj.scale *= 0.9;
+/// Eases the camera towards the CameraFollows body, moving on to whatever it merged into
+/// and letting go when it bursts or is otherwise despawned
+#[allow(clippy::too_many_arguments)]
+fn follow_camera(
+    mut follows: ResMut<CameraFollows>,
+    claimed: Query<&Claimed>,
+    bodies: Query<(&Body, &Transform), Without<Camera>>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+    windows: Query<&Window>,
+    config: Res<ControlsConfig>,
+    game: Res<GameConfig>,
+    time: Res<Time>,
+) {
+    let Some(mut target) = follows.0 else {
+        return;
+    };
+    if let Ok(claimed) = claimed.get(target) {
+        match claimed.into {
+            Some(merged) => {
+                target = merged;
+                follows.0 = Some(merged);
+            }
+            None => {
+                follows.0 = None;
+                return;
             }
         }
-        // space is out
-        if keys.pressed(KeyCode::Space) {
-            proj.scale *= 1.1;
+    }
+    let Ok((body, body_tf)) = bodies.get(target) else {
+        follows.0 = None;
+        return;
+    };
+    let Ok((mut camera_tf, mut proj)) = camera.get_single_mut() else {
+        return;
+    };
+
+    let t = (config.follow_speed * time.delta_seconds()).min(1.0);
+    let offset = body_tf.translation.truncate() - camera_tf.translation.truncate();
+    // only the part of the offset outside the deadzone is chased
+    let chase = offset - offset.clamp_length_max(config.follow_deadzone);
+    camera_tf.translation += (chase * t).extend(0.0);
+
+    if config.follow_zoom_fit {
+        if let Ok(window) = windows.get_single() {
+            let side = window.width().min(window.height());
+            if side > 0.0 {
+                let fit = (game.pixels(body.radius) * 2.0 * FOLLOW_FIT_BODIES) / side;
+                proj.scale = config.clamp_zoom(proj.scale + (fit - proj.scale) * t);
+            }
         }
     }
 }
@@ -114,54 +338,326 @@ impl GUISelect {
     pub fn entities(&self) -> Vec<&Entity> {
         self.entities.iter().collect::<Vec<_>>()
     }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(&entity)
+    }
+
+    pub fn retain(&mut self, keep: impl FnMut(&Entity) -> bool) {
+        self.entities.retain(keep);
+    }
 }
 
-// return the entity that the mouse is hovering over on left click
+/// Converts the window cursor position into world coordinates for a 2d camera
+pub fn cursor_world_pos(
+    window: &Window,
+    camera_tf: &Transform,
+    proj: &OrthographicProjection
+) -> Option<Vec2> {
+    let mouse_pos = window.cursor_position()?;
+    let window_size = Vec2::new(window.width(), window.height());
+    let camera_pos = camera_tf.translation.truncate();
+
+    let mouse_pos = Vec2::new(
+        mouse_pos.x - window_size.x / 2.0,
+        window_size.y / 2.0 - mouse_pos.y
+    );
+    Some(camera_pos + mouse_pos * proj.scale)
+}
+
+/// Picks Snow bodies with the left mouse while drop mode is off: a click selects the body under the cursor,
+/// a drag selects everything in the box, and holding shift adds to the selection instead of replacing it.
+/// Ctrl-click also makes the body the ActiveControl.
+#[allow(clippy::too_many_arguments)]
 pub fn mouse_selection(
-    mouse_button_input: Res<Input<MouseButton>>,
+    actions: Res<ActionState>,
     windows: Query<&Window>,
     camera: Query<(&Transform, &OrthographicProjection), With<Camera>>,
+    rapier_context: Res<RapierContext>,
+    snow: Query<(), With<Snow>>,
+    dropper: Res<Dropper>,
+    mut gui_select: ResMut<GUISelect>,
+    mut inspector: ResMut<InspectorSelection>,
+    mut ac: ResMut<ActiveControl>,
+    mut drag_start: Local<Option<Vec2>>,
     mut gizmos: Gizmos,
-    keys: Res<Input<KeyCode>>,
-    mut sp: ResMut<SelectedPos>,
 ) {
-    if mouse_button_input.pressed(MouseButton::Left) {
-        let window = windows.get_single().unwrap();
-        let mouse_pos = window.cursor_position().unwrap_or(Vec2::ZERO);
-        let mouse_pos = Vec2::new(mouse_pos.x, mouse_pos.y);
-
-        let window_size = Vec2::new(window.width(), window.height());
-        let camera = camera.single();
-        let camera_pos = camera.0.translation.truncate();
-        let camera_scale = camera.1.scale;
-
-        let mouse_pos = Vec2::new(
-            mouse_pos.x - window_size.x / 2.0,
-            window_size.y / 2.0 - mouse_pos.y
+    if dropper.enabled {
+        *drag_start = None;
+        return;
+    }
+    let (Ok(window), Ok((camera_tf, proj))) = (windows.get_single(), camera.get_single()) else {
+        return;
+    };
+    let Some(pos) = cursor_world_pos(window, camera_tf, proj) else {
+        return;
+    };
+
+    if actions.just_pressed(GameAction::Select) {
+        *drag_start = Some(pos);
+    }
+    let Some(start) = *drag_start else {
+        return;
+    };
+
+    let min = start.min(pos);
+    let max = start.max(pos);
+    // anything smaller than a few screen pixels is a click
+    let dragged = (max - min).max_element() > SELECT_DRAG_PIXELS * proj.scale;
+    if dragged {
+        gizmos.rect_2d((min + max) / 2.0, 0.0, max - min, Color::YELLOW);
+    }
+
+    if !actions.just_released(GameAction::Select) {
+        return;
+    }
+    *drag_start = None;
+
+    let mut picked = Vec::new();
+    if dragged {
+        let half = (max - min) / 2.0;
+        rapier_context.intersections_with_shape(
+            min + half,
+            0.0,
+            &Collider::cuboid(half.x, half.y),
+            QueryFilter::only_dynamic(),
+            |entity| {
+                if snow.contains(entity) {
+                    picked.push(entity);
+                }
+                true
+            }
         );
-        let pos = camera_pos + mouse_pos * camera_scale;
+    } else {
+        rapier_context.intersections_with_point(pos, QueryFilter::only_dynamic(), |entity| {
+            if snow.contains(entity) {
+                picked.push(entity);
+                return false;
+            }
+            true
+        });
+    }
 
-        if mouse_button_input.just_pressed(MouseButton::Left) && keys.pressed(KeyCode::ControlLeft) {
-            dbg!("spawn requested");
-            sp.0 = Some(pos);
+    // ctrl-click takes control of the body
+    if !dragged && actions.pressed(GameAction::TakeControl) {
+        if let Some(entity) = picked.first() {
+            ac.0 = Some(*entity);
         }
+    }
 
-        // println!("scale: {:?}", camera_scale);
-        // println!("camera: {:?}", camera_pos);
-        // println!("window: {:?}", window_size);
-        // println!("mouse: {:?}", mouse_pos);
-        // println!("pos: {:?}", pos);
+    let add = actions.pressed(GameAction::AddToSelection);
+    if !add {
+        gui_select.clear();
+        inspector.clear();
+    }
+    for entity in picked {
+        gui_select.add(entity);
+        inspector.select_maybe_add(entity, true);
+    }
+}
 
-        gizmos.circle_2d(pos, 100.0, Color::BLACK);
+/// Rings around the selected bodies, dropping any that have since merged away, and around the controlled one
+fn highlight_selection(
+    mut gui_select: ResMut<GUISelect>,
+    mut inspector: ResMut<InspectorSelection>,
+    entities: &Entities,
+    ac: Res<ActiveControl>,
+    bodies: Query<(&Body, &Transform)>,
+    config: Res<GameConfig>,
+    mut gizmos: Gizmos,
+) {
+    gui_select.retain(|entity| bodies.contains(*entity));
+    // the inspector can select more than bodies, so only drop what no longer exists
+    let dead = inspector
+        .iter()
+        .filter(|entity| !entities.contains(*entity))
+        .collect::<Vec<_>>();
+    for entity in dead {
+        inspector.remove(entity);
+    }
+    for entity in gui_select.entities() {
+        if let Ok((body, tf)) = bodies.get(*entity) {
+            let radius = config.pixels(body.radius) + OUTLINE_WIDTH * 2.0;
+            gizmos.circle_2d(tf.translation.truncate(), radius, Color::YELLOW);
+        }
+    }
+    if let Some(Ok((body, tf))) = ac.0.map(|entity| bodies.get(entity)) {
+        let radius = config.pixels(body.radius) + OUTLINE_WIDTH * 4.0;
+        gizmos.circle_2d(tf.translation.truncate(), radius, Color::CYAN);
+    }
+}
 
-        // rapier_context.intersections_with_point(pos, QueryFilter::new(), |entity| {
-        //     gui_select.add(entity);
-        //     if keys.pressed(KeyCode::ControlLeft) {
-        //         if let Ok(entity) = agents.get(entity) {
-        //             ac.0 = Some(entity.clone());
-        //         }
-        //     }
-        //     true
-        // });
+/// Arrow keys drive the ActiveControl body: left and right push and roll it, down pushes it down
+/// and up gives it a kick upwards. Forces grow with Body::linvel and Body::angvel so every tier responds.
+fn drive_active_control(
+    actions: Res<ActionState>,
+    ac: Res<ActiveControl>,
+    mut bodies: Query<(&Body, &mut ExternalForce, &mut ExternalImpulse)>,
+    mut driven: Local<Option<Entity>>,
+    config: Res<ControlsConfig>,
+) {
+    // let go of a body that's no longer controlled
+    if *driven != ac.0 {
+        if let Some(Ok((_, mut force, _))) = driven.map(|entity| bodies.get_mut(entity)) {
+            *force = ExternalForce::default();
+        }
+        *driven = ac.0;
     }
+    let Some(Ok((body, mut force, mut impulse))) = ac.0.map(|entity| bodies.get_mut(entity)) else {
+        return;
+    };
+
+    let dir = Vec2::new(
+        actions.axis(GameAction::DriveLeft, GameAction::DriveRight),
+        -actions.value(GameAction::DriveDown)
+    );
+
+    force.force = dir * body.linvel() * config.speed_scalar * DRIVE_FORCE;
+    // rolling right is a clockwise spin
+    force.torque = -dir.x * body.angvel() * config.speed_scalar * DRIVE_TORQUE;
+    if actions.just_pressed(GameAction::DriveJump) {
+        impulse.impulse = Vec2::Y * body.linvel() * config.speed_scalar * DRIVE_IMPULSE;
+    }
+}
+
+/// Control moves on to whatever the controlled body merged into, and ends when it bursts
+fn hand_off_control(mut ac: ResMut<ActiveControl>, claimed: Query<&Claimed>) {
+    if let Some(Ok(claimed)) = ac.0.map(|entity| claimed.get(entity)) {
+        ac.0 = claimed.into;
+    }
+}
+
+/// The body held by the right mouse button and the kinematic anchor it is pulled towards
+#[derive(Resource, Default)]
+pub struct Grab {
+    held: Option<(Entity, Entity)>,
+    last_cursor: Option<Vec2>,
+    /// Smoothed cursor velocity in pixels per second, given to the body on release
+    cursor_vel: Vec2,
+}
+
+/// A spring from the anchor to `local_anchor` on the body, critically damped so it doesn't wobble
+fn grab_joint(local_anchor: Vec2, stiffness: f32) -> GenericJoint {
+    let damping = 2.0 * stiffness.sqrt();
+    GenericJointBuilder::new(JointAxesMask::empty())
+        .local_anchor2(local_anchor)
+        .motor_position(JointAxis::X, 0.0, stiffness, damping)
+        .motor_position(JointAxis::Y, 0.0, stiffness, damping)
+        .build()
+}
+
+/// Right mouse grabs the Snow body under the cursor and drags it with physics still running,
+/// letting go throws it with the cursor's velocity
+#[allow(clippy::too_many_arguments)]
+fn drag_body(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    windows: Query<&Window>,
+    camera: Query<(&Transform, &OrthographicProjection), (With<Camera>, Without<Snow>)>,
+    rapier_context: Res<RapierContext>,
+    mut snow: Query<(&Transform, &mut Velocity, Option<&mut ImpulseJoint>), With<Snow>>,
+    mut anchors: Query<&mut Transform, (Without<Snow>, Without<Camera>)>,
+    mut grab: ResMut<Grab>,
+    config: Res<ControlsConfig>,
+    time: Res<Time>,
+) {
+    let (Ok(window), Ok((camera_tf, proj))) = (windows.get_single(), camera.get_single()) else {
+        return;
+    };
+    let cursor = cursor_world_pos(window, camera_tf, proj);
+
+    if let (Some(pos), Some(last)) = (cursor, grab.last_cursor) {
+        if time.delta_seconds() > 0.0 {
+            let vel = (pos - last) / time.delta_seconds();
+            grab.cursor_vel = grab.cursor_vel.lerp(vel, GRAB_VEL_SMOOTHING);
+        }
+    }
+    grab.last_cursor = cursor;
+
+    if let Some((body, anchor)) = grab.held {
+        // merged away while held
+        if !snow.contains(body) {
+            commands.entity(anchor).despawn();
+            grab.held = None;
+            return;
+        }
+
+        if !actions.pressed(GameAction::Grab) {
+            commands.entity(anchor).despawn();
+            commands.entity(body).remove::<ImpulseJoint>();
+            if let Ok((_, mut vel, _)) = snow.get_mut(body) {
+                vel.linvel = grab.cursor_vel;
+            }
+            grab.held = None;
+            return;
+        }
+
+        if let (Some(pos), Ok(mut anchor_tf)) = (cursor, anchors.get_mut(anchor)) {
+            anchor_tf.translation = pos.extend(anchor_tf.translation.z);
+        }
+        if config.is_changed() {
+            if let Ok((_, _, Some(mut joint))) = snow.get_mut(body) {
+                let local_anchor = joint.data.local_anchor2();
+                joint.data = grab_joint(local_anchor, config.grab_stiffness);
+            }
+        }
+        return;
+    }
+
+    let Some(pos) = cursor else {
+        return;
+    };
+    if !actions.just_pressed(GameAction::Grab) {
+        return;
+    }
+
+    let mut picked = None;
+    rapier_context.intersections_with_point(pos, QueryFilter::only_dynamic(), |entity| {
+        if snow.contains(entity) {
+            picked = Some(entity);
+            return false;
+        }
+        true
+    });
+    let Some(body) = picked else {
+        return;
+    };
+    let Ok((body_tf, ..)) = snow.get(body) else {
+        return;
+    };
+
+    // hold the body where it was clicked, not by its center
+    let offset = pos - body_tf.translation.truncate();
+    let local_anchor = (body_tf.rotation.inverse() * offset.extend(0.0)).truncate();
+    let anchor = commands
+        .spawn((
+            RigidBody::KinematicPositionBased,
+            TransformBundle::from_transform(Transform::from_translation(pos.extend(VISIBLE_Z))),
+        ))
+        .id();
+    commands.entity(body).insert(ImpulseJoint::new(anchor, grab_joint(local_anchor, config.grab_stiffness)));
+    grab.held = Some((body, anchor));
+}
+
+/// Lets the action layer know when the mouse is over egui, so clicks on windows don't drop, select or grab
+fn track_pointer(mut contexts: EguiContexts, mut captured: ResMut<PointerCaptured>) {
+    captured.0 = contexts.ctx_mut().wants_pointer_input();
+}
+
+fn grab_window(mut contexts: EguiContexts, mut config: ResMut<ControlsConfig>, recorder: Option<Res<Recorder>>) {
+    egui::Window
+        ::new("Grab")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            if recorder.is_some() {
+                ui.label("Grabbing is off while recording");
+            } else {
+                ui.label("Right drag a body to move it");
+            }
+            let mut stiffness = config.grab_stiffness;
+            ui.add(egui::Slider::new(&mut stiffness, 10.0..=2000.0).logarithmic(true).text("Strength"));
+            if stiffness != config.grab_stiffness {
+                config.grab_stiffness = stiffness;
+            }
+        });
 }
@@ -0,0 +1,182 @@
+use bevy_egui::{ egui, EguiContexts };
+
+use crate::{
+    prelude::*,
+    game::{ pause_physics, GameState },
+    input::{ ActionState, GameAction },
+    replay::{ recording, replaying, Recorder, SimFrame },
+};
+
+pub const MIN_TIME_SCALE: f32 = 0.1;
+pub const MAX_TIME_SCALE: f32 = 4.0;
+/// Fixed steps happen at most once a frame, so they can only be slowed down
+pub const MAX_FIXED_TIME_SCALE: f32 = 1.0;
+
+/// Physics pause, single stepping and time scale for looking into glitches.
+/// P pauses physics, N steps it once while paused, [ and ] halve and double the time scale.
+/// All of it is off while recording, since replays don't carry it.
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimControl>().add_systems(
+            Update,
+            (
+                debug_keys.run_if(not(recording)),
+                apply_time_scale.after(debug_keys),
+                // replays hold physics back themselves
+                hold_physics
+                    .after(debug_keys)
+                    .after(pause_physics)
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(not(replaying)),
+            )
+        );
+        if has_egui(app) {
+            app.add_systems(Update, debug_window.before(apply_time_scale).before(hold_physics));
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct SimControl {
+    pub paused: bool,
+    /// Take one physics step on the next frame while paused
+    pub step: bool,
+    pub time_scale: f32,
+    /// Fixed steps owed at the current time scale
+    ticks: f32,
+}
+
+impl Default for SimControl {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            step: false,
+            time_scale: 1.0,
+            ticks: 0.0,
+        }
+    }
+}
+
+/// The fastest time scale `mode` can run at
+pub fn max_time_scale(mode: &TimestepMode) -> f32 {
+    match mode {
+        TimestepMode::Fixed { .. } => MAX_FIXED_TIME_SCALE,
+        _ => MAX_TIME_SCALE,
+    }
+}
+
+impl SimControl {
+    pub fn set_time_scale(&mut self, scale: f32, max: f32) {
+        self.time_scale = scale.clamp(MIN_TIME_SCALE, max);
+    }
+}
+
+fn debug_keys(actions: Res<ActionState>, mut control: ResMut<SimControl>, rapier_config: Res<RapierConfiguration>) {
+    let max = max_time_scale(&rapier_config.timestep_mode);
+    if actions.just_pressed(GameAction::PausePhysics) {
+        control.paused = !control.paused;
+    }
+    if actions.just_pressed(GameAction::StepPhysics) && control.paused {
+        control.step = true;
+    }
+    if actions.just_pressed(GameAction::SlowDown) {
+        let scale = control.time_scale * 0.5;
+        control.set_time_scale(scale, max);
+    }
+    if actions.just_pressed(GameAction::SpeedUp) {
+        let scale = control.time_scale * 2.0;
+        control.set_time_scale(scale, max);
+    }
+}
+
+/// While paused, physics only runs on frames that asked for a step.
+/// With a fixed timestep the time scale skips frames instead of changing dt, the same way replays
+/// are slowed down, so every step stays the one a recording took.
+fn hold_physics(
+    mut control: ResMut<SimControl>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    time: Res<Time>,
+    setup: Res<SetupConfig>
+) {
+    if control.paused {
+        rapier_config.physics_pipeline_active = control.step;
+        control.step = false;
+        control.ticks = 0.0;
+        return;
+    }
+    if !matches!(rapier_config.timestep_mode, TimestepMode::Fixed { .. }) {
+        rapier_config.physics_pipeline_active = true;
+        return;
+    }
+    let scale = control.time_scale.min(MAX_FIXED_TIME_SCALE);
+    control.ticks = (control.ticks + (time.delta_seconds() * scale) / setup.fixed_dt).min(1.0);
+    let tick = control.ticks >= 1.0;
+    rapier_config.physics_pipeline_active = tick;
+    if tick {
+        control.ticks -= 1.0;
+    }
+}
+
+/// Variable and interpolated timesteps scale the time rapier is handed instead, fixed ones are left to hold_physics
+fn apply_time_scale(
+    control: Res<SimControl>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    setup: Res<SetupConfig>
+) {
+    if !control.is_changed() {
+        return;
+    }
+    match &mut rapier_config.timestep_mode {
+        TimestepMode::Fixed { .. } => {}
+        TimestepMode::Variable { max_dt, time_scale, .. } => {
+            // a frame's worth of time is capped at max_dt, which would cap speedups too
+            *max_dt = setup.fixed_dt * control.time_scale.max(1.0);
+            *time_scale = control.time_scale;
+        }
+        TimestepMode::Interpolated { time_scale, .. } => {
+            *time_scale = control.time_scale;
+        }
+    }
+}
+
+fn debug_window(
+    mut contexts: EguiContexts,
+    mut control: ResMut<SimControl>,
+    frame: Res<SimFrame>,
+    rapier_config: Res<RapierConfiguration>,
+    recorder: Option<Res<Recorder>>
+) {
+    let max = max_time_scale(&rapier_config.timestep_mode);
+    egui::Window
+        ::new("Debug")
+        .default_pos([0.0, 300.0])
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("Physics step {}", frame.0));
+            if recorder.is_some() {
+                ui.label("Off while recording");
+                return;
+            }
+            ui.checkbox(&mut control.paused, "Pause physics (P)");
+            ui.add_enabled_ui(control.paused, |ui| {
+                if ui.button("Step (N)").clicked() {
+                    control.step = true;
+                }
+            });
+            let mut scale = control.time_scale;
+            ui.add(
+                egui::Slider
+                    ::new(&mut scale, MIN_TIME_SCALE..=max)
+                    .logarithmic(true)
+                    .text("Time scale ([ ])")
+            );
+            if ui.button("Reset to 1x").clicked() {
+                scale = 1.0;
+            }
+            if scale != control.time_scale {
+                control.set_time_scale(scale, max);
+            }
+        });
+}
@@ -0,0 +1,144 @@
+use bevy_egui::{ egui, EguiContexts };
+
+use crate::{ prelude::*, logic::{ body::Body, score::Score, undo::History }, replay::replaying };
+
+use super::{ snow::Snow, GameState };
+
+pub struct DangerPlugin;
+
+impl Plugin for DangerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Danger>()
+            .add_event::<RestartGame>()
+            .add_systems(Update, (watch_danger_line.run_if(in_state(GameState::Playing)), restart));
+        if !is_headless(app) {
+            app.add_systems(Update, draw_danger_line);
+        }
+        if has_egui(app) {
+            app.add_systems(Update, game_over_ui.run_if(in_state(GameState::GameOver)).run_if(not(replaying)));
+        }
+    }
+}
+
+/// Clears the board and starts a new run
+#[derive(Event, Clone, Copy, Debug, Default)]
+pub struct RestartGame;
+
+/// Bodies currently resting above the danger line and for how long
+#[derive(Resource, Default)]
+pub struct Danger {
+    pub over: HashMap<Entity, f32>,
+}
+
+impl Danger {
+    pub fn active(&self) -> bool {
+        !self.over.is_empty()
+    }
+
+    /// The longest any body has stayed over the line
+    pub fn longest(&self) -> f32 {
+        self.over.values().copied().fold(0.0, f32::max)
+    }
+}
+
+/// A body counts once it has settled with its top edge over the line,
+/// so pieces still falling in from the drop point are ignored
+fn watch_danger_line(
+    snow: Query<(Entity, &Body, &Transform, &Velocity), With<Snow>>,
+    mut danger: ResMut<Danger>,
+    mut next_state: ResMut<NextState<GameState>>,
+    time: Res<Time>,
+    rapier_config: Res<RapierConfiguration>,
+    config: Res<GameConfig>
+) {
+    // counted in simulated time, so held or fixed step frames match a recording
+    if !rapier_config.physics_pipeline_active {
+        return;
+    }
+    let dt = match rapier_config.timestep_mode {
+        TimestepMode::Fixed { dt, .. } => dt,
+        _ => time.delta_seconds(),
+    };
+
+    let mut still_over = HashMap::new();
+    for (entity, body, tf, vel) in snow.iter() {
+        let top = tf.translation.y + config.pixels(body.radius);
+        if top > config.danger_line_y && vel.linvel.length() < config.settled_speed {
+            let over = danger.over.get(&entity).copied().unwrap_or(0.0);
+            still_over.insert(entity, over + dt);
+        }
+    }
+    danger.over = still_over;
+
+    if danger.longest() > config.danger_grace {
+        info!("Game over, body stayed above the danger line for {}s", config.danger_grace);
+        next_state.set(GameState::GameOver);
+    }
+}
+
+fn draw_danger_line(
+    mut gizmos: Gizmos,
+    danger: Res<Danger>,
+    time: Res<Time>,
+    config: Res<GameConfig>
+) {
+    let color = if danger.active() {
+        let pulse = 0.5 + 0.5 * (time.elapsed_seconds() * DANGER_PULSE_SPEED).sin();
+        Color::rgba(1.0, 0.0, 0.0, 0.25 + 0.75 * pulse)
+    } else {
+        Color::rgba(1.0, 1.0, 1.0, 0.25)
+    };
+    gizmos.line_2d(
+        Vec2::new(-config.floor_half_x, config.danger_line_y),
+        Vec2::new(config.floor_half_x, config.danger_line_y),
+        color
+    );
+}
+
+fn game_over_ui(
+    mut contexts: EguiContexts,
+    score: Res<Score>,
+    mut restart: EventWriter<RestartGame>
+) {
+    egui::Window
+        ::new("Game Over")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.heading(format!("Score: {}", score.points));
+            ui.label(format!("Merges: {}", score.merges));
+            ui.label(format!("Best chain: {}", score.best_chain));
+            ui.separator();
+            if ui.button("Restart").clicked() {
+                restart.send(RestartGame);
+            }
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn restart(
+    mut events: EventReader<RestartGame>,
+    mut commands: Commands,
+    mut score: ResMut<Score>,
+    mut danger: ResMut<Danger>,
+    mut history: ResMut<History>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    snow: Query<Entity, With<Snow>>
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+    for entity in snow.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    score.reset();
+    danger.over.clear();
+    history.clear();
+    // leaving for the main menu clears the board too, but stays on the menu
+    let to_menu = *state.get() == GameState::MainMenu || next_state.0 == Some(GameState::MainMenu);
+    if !to_menu {
+        next_state.set(GameState::Playing);
+    }
+}
@@ -0,0 +1,121 @@
+use bevy::app::AppExit;
+use bevy_egui::{ egui, EguiContexts };
+
+use crate::{
+    prelude::*,
+    input::{ keymap_window, rebinding_open, ActionState, GameAction, Rebinding },
+    logic::{ save::{ LoadGame, SaveGame }, score::Score },
+    replay::Recorder,
+};
+
+use super::{ danger::RestartGame, GameState };
+
+/// Main menu and pause screens, Esc pauses and resumes. Both open the Controls screen for rebinding.
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        if is_headless(app) {
+            return;
+        }
+        app.add_systems(Update, toggle_pause);
+        if !has_egui(app) {
+            return;
+        }
+        app.add_systems(Update, (
+            main_menu.run_if(in_state(GameState::MainMenu)),
+            pause_menu.run_if(in_state(GameState::Paused)),
+            keymap_window.run_if(rebinding_open),
+        ));
+    }
+}
+
+fn toggle_pause(
+    actions: Res<ActionState>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>
+) {
+    if !actions.just_pressed(GameAction::Pause) {
+        return;
+    }
+    match state.get() {
+        GameState::Playing => next_state.set(GameState::Paused),
+        GameState::Paused => next_state.set(GameState::Playing),
+        _ => {}
+    }
+}
+
+fn menu_window(title: &str) -> egui::Window<'static> {
+    egui::Window
+        ::new(title)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+}
+
+fn main_menu(
+    mut contexts: EguiContexts,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut load: EventWriter<LoadGame>,
+    mut exit: EventWriter<AppExit>,
+    mut rebinding: ResMut<Rebinding>,
+    config: Res<LogicConfig>,
+    recorder: Option<Res<Recorder>>
+) {
+    menu_window("yuki").show(contexts.ctx_mut(), |ui| {
+        if ui.button("Play").clicked() {
+            next_state.set(GameState::Playing);
+        }
+        if ui.add_enabled(recorder.is_none(), egui::Button::new("Load quick save")).clicked() {
+            load.send(LoadGame(config.save_path.clone()));
+        }
+        if ui.button("Controls").clicked() {
+            rebinding.open = !rebinding.open;
+        }
+        if ui.button("Quit").clicked() {
+            exit.send(AppExit);
+        }
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn pause_menu(
+    mut contexts: EguiContexts,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut restart: EventWriter<RestartGame>,
+    mut save: EventWriter<SaveGame>,
+    mut load: EventWriter<LoadGame>,
+    mut exit: EventWriter<AppExit>,
+    mut rebinding: ResMut<Rebinding>,
+    score: Res<Score>,
+    config: Res<LogicConfig>,
+    recorder: Option<Res<Recorder>>
+) {
+    menu_window("Paused").show(contexts.ctx_mut(), |ui| {
+        ui.label(format!("Score: {}", score.points));
+        ui.separator();
+        if ui.button("Resume (Esc)").clicked() {
+            next_state.set(GameState::Playing);
+        }
+        if ui.button("Restart").clicked() {
+            restart.send(RestartGame);
+        }
+        if ui.button("Quick save").clicked() {
+            save.send(SaveGame(config.save_path.clone()));
+        }
+        if ui.add_enabled(recorder.is_none(), egui::Button::new("Load quick save")).clicked() {
+            load.send(LoadGame(config.save_path.clone()));
+        }
+        if ui.button("Controls").clicked() {
+            rebinding.open = !rebinding.open;
+        }
+        if ui.button("Main menu").clicked() {
+            // so Play starts a fresh run
+            restart.send(RestartGame);
+            next_state.set(GameState::MainMenu);
+        }
+        if ui.button("Quit").clicked() {
+            exit.send(AppExit);
+        }
+    });
+}
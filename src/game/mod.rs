@@ -1,19 +1,48 @@
 use crate::{
-    prelude::*, logic::{assets::GeneratedAssets, hud::SelectedPos},
+    prelude::*, logic::{assets::{GeneratedAssets, init_assets}, tiers::TierTable},
 };
 
 pub mod snow;
+pub mod danger;
+pub mod menu;
 use snow::Snow;
 
 
-pub struct GamePlugin;
+#[derive(Default)]
+pub struct GamePlugin {
+    pub config: GameConfig,
+}
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, initialize);
+        // nobody is there to press Play when headless, or without the egui menus
+        if !has_egui(app) {
+            app.insert_resource(State::new(GameState::Playing));
+        }
+        // SetupPlugin may be left out for the host's own setup, timings then keep their defaults
+        app.insert_resource(self.config.clone())
+            .init_resource::<SetupConfig>()
+            .add_state::<GameState>()
+            .add_plugins((danger::DangerPlugin, menu::MenuPlugin))
+            .add_systems(Startup, initialize.after(init_assets))
+            .add_systems(Update, pause_physics.run_if(state_changed::<GameState>()));
     }
 }
 
+#[derive(States, Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum GameState {
+    #[default]
+    MainMenu,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+/// Physics only steps while Playing
+pub fn pause_physics(state: Res<State<GameState>>, mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = *state.get() == GameState::Playing;
+}
+
 
 
 #[derive(Component)]
@@ -22,41 +51,38 @@ pub struct Wall;
 
 pub fn initialize(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    assets: Res<GeneratedAssets>
+    assets: Res<GeneratedAssets>,
+    tiers: Res<TierTable>,
+    config: Res<GameConfig>
 ) {
     // Create the map
 
-    let wall_collider: Collider = Collider::cuboid(wall_half_x, wall_half_y);
-    let floor_collider: Collider = Collider::cuboid(floor_half_x, floor_half_y);
-
-    let color = &assets.colors.get("BLACK").expect("Colors always exist").1;
-    let wall_mesh = assets.meshes.get("WALL").expect("meshes");
-    let floor_mesh = assets.meshes.get("FLOOR").expect("floor");
-    // left
-
-    let pos = Transform::from_translation(Vec3::new(-floor_half_x, 0.0, VISIBLE_Z));
-    let render = make_render(wall_mesh.clone(), color.clone(), pos);
-    commands.spawn(Wall).insert(RigidBody::Fixed).insert(wall_collider.clone()).insert(render);
-
-    // right
-    let pos = Transform::from_translation(Vec3::new(floor_half_x, 0.0, VISIBLE_Z));
-    let render = make_render(wall_mesh.clone(), color.clone(), pos);
-    commands.spawn(Wall).insert(RigidBody::Fixed).insert(wall_collider).insert(render);
-
-    // bottom
-    let pos = Transform::from_translation(Vec3::new(0.0, -wall_half_y, VISIBLE_Z));
-    let render = make_render(floor_mesh.clone(), color.clone(), pos);
-    commands.spawn(Wall).insert(RigidBody::Fixed).insert(floor_collider).insert(render);
-
-
+    let wall_collider: Collider = Collider::cuboid(config.wall_half_x, config.wall_half_y);
+    let floor_collider: Collider = Collider::cuboid(config.floor_half_x, config.floor_half_y);
+
+    let walls = [
+        // left
+        (Vec3::new(-config.floor_half_x, 0.0, VISIBLE_Z), wall_collider.clone(), "WALL"),
+        // right
+        (Vec3::new(config.floor_half_x, 0.0, VISIBLE_Z), wall_collider, "WALL"),
+        // bottom
+        (Vec3::new(0.0, -config.wall_half_y, VISIBLE_Z), floor_collider, "FLOOR"),
+    ];
+
+    let color = assets.material("BLACK");
+    for (pos, collider, mesh) in walls {
+        let pos = Transform::from_translation(pos);
+        let mut ec = commands.spawn(Wall);
+        ec.insert(RigidBody::Fixed).insert(collider).insert(TransformBundle::from_transform(pos));
+        if let (Some(mesh), Some(color)) = (assets.meshes.get(mesh), color.clone()) {
+            ec.insert(make_render(mesh.clone(), color, pos));
+        }
+    }
 
     // Add 1 agent to the center of the world
 
     let pos = Vec2::ZERO;
     let dir = Vec2::Y;
-    let color = String::from("WHITE");
-    Snow::spawn(pos, dir, color, &mut commands, &assets, 2.0);
+    Snow::spawn(pos, dir, 0, &mut commands, &assets, &tiers, &config);
 }
 
@@ -1,46 +1,131 @@
-use crate::{prelude::*, logic::{assets::GeneratedAssets, body::BodyBundle}};
+use crate::{prelude::*, logic::{assets::GeneratedAssets, body::BodyBundle, tiers::TierTable}};
 
 
+#[derive(Component)]
+pub struct Snow;
+
 #[derive(Bundle)]
-pub struct Snow {
-    ace: ActiveEvents
+pub struct SnowBundle {
+    snow: Snow,
+    ace: ActiveEvents,
+    mass: ReadMassProperties,
 }
 
-
-impl Snow {
+impl SnowBundle {
     pub fn new() -> Self {
         Self {
-            ace: ActiveEvents::COLLISION_EVENTS
+            snow: Snow,
+            ace: ActiveEvents::COLLISION_EVENTS,
+            mass: ReadMassProperties::default(),
         }
     }
+}
 
+
+impl Snow {
     pub fn spawn(
         pos: Vec2,
         dir: Vec2,
-        color: String,
+        tier: usize,
         commands: &mut Commands,
         assets: &Res<GeneratedAssets>,
-        size: f32,
+        tiers: &TierTable,
+        config: &GameConfig,
     ) -> Entity {
-        dbg!("spawning");
-        let mesh = assets.meshes.get((size).to_string().as_str()).expect(format!("Size exists {}", (size).to_string()).as_str());
-        let (color, material) = assets.colors.get(&color).unwrap();
+        let def = tiers.get(tier).expect(format!("Tier exists {}", tier).as_str());
+        let color = assets.color(&def.color);
         let body = BodyBundle::spawn(
-            size,
-            *color,
+            tier,
+            def.radius,
+            color,
             pos,
             dir,
-            mesh.clone(),
-            material.clone(),
-            commands
+            assets.tier_render(tier, &def.color),
+            commands,
+            config
         );
 
         if let Some(mut ec) = commands.get_entity(body) {
-            ec.insert(Snow::new());
+            ec.insert(SnowBundle::new())
+                .insert(ColliderMassProperties::Density(def.density))
+                .insert(Restitution::coefficient(def.restitution))
+                .insert(Friction::coefficient(def.friction));
         } else {
             warn!("Spawned entity not found");
         }
 
+        if assets.rendered() {
+            Snow::spawn_face(body, tier, config.pixels(def.radius), &def.color, commands, assets);
+        }
+
         body
     }
+
+    /// Outline ring and face children, drawn with the tier's inverted color. `radius` is in pixels.
+    fn spawn_face(
+        body: Entity,
+        tier: usize,
+        radius: f32,
+        color: &str,
+        commands: &mut Commands,
+        assets: &Res<GeneratedAssets>,
+    ) {
+        let outline = assets.tier_outlines.get(tier).expect(format!("Tier outline exists {}", tier).as_str());
+        let amaterial = assets.material(&format!("A{}", color)).expect("Inverted colors always exist");
+        let eyeball = assets.meshes.get("EYEBALL").expect("eyeball");
+
+        commands.entity(body).with_children(|parent| {
+            // outline ring, drawn behind the fill
+            parent.spawn(Render {
+                material: amaterial.clone(),
+                mesh: outline.clone(),
+                transform: Transform::from_xyz(0.0, 0.0, -CHILD_VISIBLE_Z),
+                ..Default::default()
+            });
+
+            let eye = Snow::eye_radius(radius);
+            for eye_pos in [Snow::left_eye_pos(radius), Snow::right_eye_pos(radius)] {
+                parent.spawn(Render {
+                    material: amaterial.clone(),
+                    mesh: eyeball.clone(),
+                    transform: Transform::from_translation(eye_pos.extend(CHILD_VISIBLE_Z))
+                        .with_scale(Vec3::splat(eye)),
+                    ..Default::default()
+                });
+            }
+
+            // squashed circle for a mouth
+            parent.spawn(Render {
+                material: amaterial.clone(),
+                mesh: eyeball.clone(),
+                transform: Transform::from_translation(Snow::mouth_pos(radius).extend(CHILD_VISIBLE_Z))
+                    .with_scale(Vec3::new(eye * 2.0, eye * 0.5, 1.0)),
+                ..Default::default()
+            });
+        });
+    }
+
+    pub fn eye_radius(radius: f32) -> f32 {
+        radius * 0.12
+    }
+
+    pub fn eye_dist(radius: f32) -> f32 {
+        radius * 0.25
+    }
+
+    pub fn eye_offset(radius: f32) -> f32 {
+        radius * 0.35
+    }
+
+    pub fn left_eye_pos(radius: f32) -> Vec2 {
+        Vec2::new(-Snow::eye_offset(radius), Snow::eye_dist(radius))
+    }
+
+    pub fn right_eye_pos(radius: f32) -> Vec2 {
+        Vec2::new(Snow::eye_offset(radius), Snow::eye_dist(radius))
+    }
+
+    pub fn mouth_pos(radius: f32) -> Vec2 {
+        Vec2::new(0.0, -Snow::eye_dist(radius))
+    }
 }
@@ -0,0 +1,585 @@
+use std::{ collections::BTreeMap, fmt, path::Path };
+
+use bevy::{ ecs::system::SystemParam, input::InputSystem };
+use bevy_egui::{ egui, EguiContexts };
+use serde::{ Deserialize, Serialize };
+
+use crate::prelude::*;
+
+/// How far a stick has to be pushed to be picked up while rebinding
+pub const REBIND_AXIS_THRESHOLD: f32 = 0.5;
+/// Sticks and triggers that can be bound, checked in this order while rebinding
+pub const BINDABLE_AXES: [GamepadAxisType; 6] = [
+    GamepadAxisType::LeftStickX,
+    GamepadAxisType::LeftStickY,
+    GamepadAxisType::RightStickX,
+    GamepadAxisType::RightStickY,
+    GamepadAxisType::LeftZ,
+    GamepadAxisType::RightZ,
+];
+
+/// Turns keys, mouse buttons and gamepad input into GameActions through the Keymap,
+/// so the game asks what the player wants to do rather than which key they pressed
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        // an embedding app or test can insert its own keymap first
+        if !app.world.contains_resource::<Keymap>() {
+            let path = app.world.get_resource::<ControlsConfig>().cloned().unwrap_or_default().keymap_path;
+            app.insert_resource(Keymap::load_or_default(&path));
+        }
+        app.init_resource::<ActionState>()
+            .init_resource::<PointerCaptured>()
+            .init_resource::<Rebinding>()
+            .add_systems(PreUpdate, update_actions.after(InputSystem));
+    }
+}
+
+/// Everything the player can do, each bound to any number of inputs in the Keymap
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GameAction {
+    CameraPanUp,
+    CameraPanDown,
+    CameraPanLeft,
+    CameraPanRight,
+    /// Held to pan the camera with the mouse
+    DragPan,
+    ZoomIn,
+    ZoomOut,
+    CenterCamera,
+    Drop,
+    AimLeft,
+    AimRight,
+    ToggleDropMode,
+    SwapHold,
+    Select,
+    /// Held while selecting to add to the selection
+    AddToSelection,
+    /// Held while selecting to take control of the body
+    TakeControl,
+    Grab,
+    DriveLeft,
+    DriveRight,
+    DriveDown,
+    DriveJump,
+    ToggleInspector,
+    QuickSave,
+    QuickLoad,
+    Undo,
+    Pause,
+    PausePhysics,
+    StepPhysics,
+    SlowDown,
+    SpeedUp,
+}
+
+impl GameAction {
+    /// Every action, in the order the rebinding screen lists them
+    pub const ALL: [GameAction; 30] = [
+        GameAction::CameraPanUp,
+        GameAction::CameraPanDown,
+        GameAction::CameraPanLeft,
+        GameAction::CameraPanRight,
+        GameAction::DragPan,
+        GameAction::ZoomIn,
+        GameAction::ZoomOut,
+        GameAction::CenterCamera,
+        GameAction::Drop,
+        GameAction::AimLeft,
+        GameAction::AimRight,
+        GameAction::ToggleDropMode,
+        GameAction::SwapHold,
+        GameAction::Select,
+        GameAction::AddToSelection,
+        GameAction::TakeControl,
+        GameAction::Grab,
+        GameAction::DriveLeft,
+        GameAction::DriveRight,
+        GameAction::DriveDown,
+        GameAction::DriveJump,
+        GameAction::ToggleInspector,
+        GameAction::QuickSave,
+        GameAction::QuickLoad,
+        GameAction::Undo,
+        GameAction::Pause,
+        GameAction::PausePhysics,
+        GameAction::StepPhysics,
+        GameAction::SlowDown,
+        GameAction::SpeedUp,
+    ];
+}
+
+/// One input an action can be bound to. Gamepad bindings listen to every connected gamepad.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Pad(GamepadButtonType),
+    /// A stick or trigger pushed towards positive values
+    AxisPositive(GamepadAxisType),
+    /// A stick or trigger pushed towards negative values
+    AxisNegative(GamepadAxisType),
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{:?}", key),
+            Binding::Mouse(button) => write!(f, "Mouse {:?}", button),
+            Binding::Pad(button) => write!(f, "Pad {:?}", button),
+            Binding::AxisPositive(axis) => write!(f, "Pad {:?}+", axis),
+            Binding::AxisNegative(axis) => write!(f, "Pad {:?}-", axis),
+        }
+    }
+}
+
+/// Which inputs trigger which actions, read from ControlsConfig::keymap_path on startup, see assets/keymap.ron
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Keymap {
+    /// Stick travel ignored around the center, from 0 to 1
+    pub deadzone: f32,
+    pub bindings: BTreeMap<GameAction, Vec<Binding>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use GameAction as A;
+        use Binding::{ Key, Mouse, Pad, AxisPositive as Pos, AxisNegative as Neg };
+        use GamepadAxisType::*;
+
+        let bindings = [
+            (A::CameraPanUp, vec![Key(KeyCode::W), Pos(RightStickY)]),
+            (A::CameraPanDown, vec![Key(KeyCode::S), Neg(RightStickY)]),
+            (A::CameraPanLeft, vec![Key(KeyCode::A), Neg(RightStickX)]),
+            (A::CameraPanRight, vec![Key(KeyCode::D), Pos(RightStickX)]),
+            (A::DragPan, vec![Mouse(MouseButton::Middle)]),
+            (A::ZoomIn, vec![Key(KeyCode::Equals), Key(KeyCode::NumpadAdd), Pad(GamepadButtonType::RightTrigger)]),
+            (A::ZoomOut, vec![Key(KeyCode::Space), Pad(GamepadButtonType::LeftTrigger)]),
+            (A::CenterCamera, vec![Key(KeyCode::C), Pad(GamepadButtonType::RightThumb)]),
+            (A::Drop, vec![Key(KeyCode::Space), Mouse(MouseButton::Left), Pad(GamepadButtonType::South)]),
+            (A::AimLeft, vec![Key(KeyCode::A), Neg(LeftStickX), Pad(GamepadButtonType::DPadLeft)]),
+            (A::AimRight, vec![Key(KeyCode::D), Pos(LeftStickX), Pad(GamepadButtonType::DPadRight)]),
+            (A::ToggleDropMode, vec![Key(KeyCode::Tab), Pad(GamepadButtonType::North)]),
+            (A::SwapHold, vec![Key(KeyCode::Q), Pad(GamepadButtonType::West)]),
+            (A::Select, vec![Mouse(MouseButton::Left)]),
+            (A::AddToSelection, vec![Key(KeyCode::ShiftLeft), Key(KeyCode::ShiftRight)]),
+            (A::TakeControl, vec![Key(KeyCode::ControlLeft), Key(KeyCode::ControlRight)]),
+            (A::Grab, vec![Mouse(MouseButton::Right)]),
+            (A::DriveLeft, vec![Key(KeyCode::Left)]),
+            (A::DriveRight, vec![Key(KeyCode::Right)]),
+            (A::DriveDown, vec![Key(KeyCode::Down)]),
+            (A::DriveJump, vec![Key(KeyCode::Up)]),
+            (A::ToggleInspector, vec![Key(KeyCode::I)]),
+            (A::QuickSave, vec![Key(KeyCode::F5)]),
+            (A::QuickLoad, vec![Key(KeyCode::F9)]),
+            (A::Undo, vec![Key(KeyCode::Z), Pad(GamepadButtonType::East)]),
+            (A::Pause, vec![Key(KeyCode::Escape), Pad(GamepadButtonType::Start)]),
+            (A::PausePhysics, vec![Key(KeyCode::P)]),
+            (A::StepPhysics, vec![Key(KeyCode::N)]),
+            (A::SlowDown, vec![Key(KeyCode::BracketLeft)]),
+            (A::SpeedUp, vec![Key(KeyCode::BracketRight)]),
+        ];
+        Self {
+            deadzone: 0.2,
+            bindings: bindings.into_iter().collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Actions left out of the file keep their default bindings
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs
+            ::read_to_string(path)
+            .map_err(|e| format!("Couldn't read keymap {}: {}", path, e))?;
+        let mut keymap: Keymap = ron
+            ::from_str(&text)
+            .map_err(|e| format!("Couldn't parse keymap {}: {}", path, e))?;
+        for (action, bindings) in Keymap::default().bindings {
+            keymap.bindings.entry(action).or_insert(bindings);
+        }
+        Ok(keymap)
+    }
+
+    /// The default keymap when there's no file, or when it doesn't parse
+    pub fn load_or_default(path: &str) -> Self {
+        if !Path::new(path).exists() {
+            return Keymap::default();
+        }
+        Keymap::load(path).unwrap_or_else(|e| {
+            warn!("{}, using the default keymap", e);
+            Keymap::default()
+        })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = ron::ser
+            ::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| format!("Couldn't write keymap {}: {}", path, e))
+    }
+
+    pub fn bindings(&self, action: GameAction) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], |b| b.as_slice())
+    }
+
+    pub fn bind(&mut self, action: GameAction, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: GameAction, index: usize) {
+        if let Some(bindings) = self.bindings.get_mut(&action) {
+            if index < bindings.len() {
+                bindings.remove(index);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct ActionValue {
+    value: f32,
+    pressed: bool,
+    just_pressed: bool,
+    just_released: bool,
+}
+
+/// This frame's state of every action, updated in PreUpdate
+#[derive(Resource, Debug, Default)]
+pub struct ActionState {
+    actions: BTreeMap<GameAction, ActionValue>,
+}
+
+impl ActionState {
+    fn get(&self, action: GameAction) -> ActionValue {
+        self.actions.get(&action).copied().unwrap_or_default()
+    }
+
+    pub fn pressed(&self, action: GameAction) -> bool {
+        self.get(action).pressed
+    }
+
+    pub fn just_pressed(&self, action: GameAction) -> bool {
+        self.get(action).just_pressed
+    }
+
+    pub fn just_released(&self, action: GameAction) -> bool {
+        self.get(action).just_released
+    }
+
+    /// How hard the action is pushed, from 0 to 1. Keys and buttons are all or nothing.
+    pub fn value(&self, action: GameAction) -> f32 {
+        self.get(action).value
+    }
+
+    /// `positive` minus `negative`, from -1 to 1
+    pub fn axis(&self, negative: GameAction, positive: GameAction) -> f32 {
+        self.value(positive) - self.value(negative)
+    }
+
+    /// Moves the action on to `value`, pressed when above 0
+    fn set(&mut self, action: GameAction, value: f32, edges: bool) {
+        let state = self.actions.entry(action).or_default();
+        let pressed = value > 0.0;
+        state.just_pressed = edges && pressed && !state.pressed;
+        state.just_released = edges && !pressed && state.pressed;
+        state.pressed = pressed;
+        state.value = value;
+    }
+}
+
+/// Set while the mouse is over the ui, so clicks on windows don't drop or select
+#[derive(Resource, Default)]
+pub struct PointerCaptured(pub bool);
+
+/// The rebinding screen, opened from the menus
+#[derive(Resource, Default)]
+pub struct Rebinding {
+    pub open: bool,
+    /// Waiting for an input to bind to this action
+    pub listening: Option<GameAction>,
+}
+
+pub fn rebinding_open(rebinding: Res<Rebinding>) -> bool {
+    rebinding.open
+}
+
+/// bevy's input resources, read through Bindings
+#[derive(SystemParam)]
+pub struct RawInput<'w> {
+    keys: Res<'w, Input<KeyCode>>,
+    mouse: Res<'w, Input<MouseButton>>,
+    gamepads: Res<'w, Gamepads>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+}
+
+impl RawInput<'_> {
+    /// How hard `binding` is pushed, from 0 to 1
+    pub fn strength(&self, binding: Binding, deadzone: f32) -> f32 {
+        let on = |pressed: bool| -> f32 { if pressed { 1.0 } else { 0.0 } };
+        match binding {
+            Binding::Key(key) => on(self.keys.pressed(key)),
+            Binding::Mouse(button) => on(self.mouse.pressed(button)),
+            Binding::Pad(button) =>
+                on(self.gamepads.iter().any(|gamepad| self.buttons.pressed(GamepadButton::new(gamepad, button)))),
+            Binding::AxisPositive(axis) => self.stick(axis, 1.0, deadzone),
+            Binding::AxisNegative(axis) => self.stick(axis, -1.0, deadzone),
+        }
+    }
+
+    /// The furthest any gamepad pushes `axis` towards `sign`, rescaled to start at the deadzone
+    fn stick(&self, axis: GamepadAxisType, sign: f32, deadzone: f32) -> f32 {
+        self.gamepads
+            .iter()
+            .filter_map(|gamepad| self.axes.get(GamepadAxis::new(gamepad, axis)))
+            .map(|value| ((value * sign - deadzone) / (1.0 - deadzone).max(f32::EPSILON)).clamp(0.0, 1.0))
+            .fold(0.0, f32::max)
+    }
+
+    /// The first input pressed this frame, for rebinding. Mouse buttons are skipped when `mouse` is false.
+    pub fn just_pressed(&self, mouse: bool) -> Option<Binding> {
+        if let Some(key) = self.keys.get_just_pressed().next() {
+            return Some(Binding::Key(*key));
+        }
+        if let Some(button) = self.mouse.get_just_pressed().next().filter(|_| mouse) {
+            return Some(Binding::Mouse(*button));
+        }
+        if let Some(button) = self.buttons.get_just_pressed().next() {
+            return Some(Binding::Pad(button.button_type));
+        }
+        for gamepad in self.gamepads.iter() {
+            for axis in BINDABLE_AXES {
+                match self.axes.get(GamepadAxis::new(gamepad, axis)) {
+                    Some(value) if value > REBIND_AXIS_THRESHOLD => {
+                        return Some(Binding::AxisPositive(axis));
+                    }
+                    Some(value) if value < -REBIND_AXIS_THRESHOLD => {
+                        return Some(Binding::AxisNegative(axis));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
+}
+
+pub fn update_actions(
+    mut actions: ResMut<ActionState>,
+    keymap: Res<Keymap>,
+    input: RawInput,
+    captured: Res<PointerCaptured>,
+    rebinding: Res<Rebinding>
+) {
+    for action in GameAction::ALL {
+        let held = actions.pressed(action);
+        let value = keymap
+            .bindings(action)
+            .iter()
+            .map(|binding| {
+                // a click on the ui doesn't start an action, but one already held carries on over it
+                if matches!(binding, Binding::Mouse(_)) && captured.0 && !held {
+                    return 0.0;
+                }
+                input.strength(*binding, keymap.deadzone)
+            })
+            .fold(0.0, f32::max);
+        // whatever is pressed to rebind an action shouldn't also trigger one
+        actions.set(action, value, rebinding.listening.is_none());
+    }
+}
+
+/// A run condition that flips each time `action` is pressed, like bevy's input_toggle_active
+pub fn action_toggle_active(default: bool, action: GameAction) -> impl FnMut(Res<ActionState>) -> bool + Clone {
+    let mut active = default;
+    move |actions: Res<ActionState>| {
+        active ^= actions.just_pressed(action);
+        active
+    }
+}
+
+/// Lists every action with its bindings: click a binding to remove it, + to bind the next input pressed
+pub fn keymap_window(
+    mut contexts: EguiContexts,
+    mut keymap: ResMut<Keymap>,
+    mut rebinding: ResMut<Rebinding>,
+    input: RawInput,
+    config: Res<ControlsConfig>
+) {
+    let ctx = contexts.ctx_mut();
+    if let Some(action) = rebinding.listening {
+        // clicks on the window are for its buttons
+        if let Some(binding) = input.just_pressed(!ctx.is_pointer_over_area()) {
+            keymap.bind(action, binding);
+            rebinding.listening = None;
+        }
+    }
+
+    let mut open = rebinding.open;
+    egui::Window
+        ::new("Controls")
+        .open(&mut open)
+        .vscroll(true)
+        .show(ctx, |ui| {
+            egui::Grid
+                ::new("keymap")
+                .striped(true)
+                .show(ui, |ui| {
+                    for action in GameAction::ALL {
+                        ui.label(format!("{:?}", action));
+                        ui.horizontal(|ui| {
+                            let mut remove = None;
+                            for (i, binding) in keymap.bindings(action).iter().enumerate() {
+                                if ui.button(binding.to_string()).on_hover_text("Click to remove").clicked() {
+                                    remove = Some(i);
+                                }
+                            }
+                            if let Some(i) = remove {
+                                keymap.unbind(action, i);
+                            }
+                            if rebinding.listening == Some(action) {
+                                if ui.button("Press an input, or click to cancel").clicked() {
+                                    rebinding.listening = None;
+                                }
+                            } else if ui.button("+").clicked() {
+                                rebinding.listening = Some(action);
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    match keymap.save(&config.keymap_path) {
+                        Ok(()) => info!("Saved keymap to {}", config.keymap_path),
+                        Err(e) => warn!("{}", e),
+                    }
+                }
+                if ui.button("Defaults").clicked() {
+                    *keymap = Keymap::default();
+                }
+            });
+        });
+    rebinding.open = open;
+    if !open {
+        rebinding.listening = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::gamepad::{
+        GamepadAxisChangedEvent,
+        GamepadButtonChangedEvent,
+        GamepadConnection,
+        GamepadConnectionEvent,
+        GamepadEvent,
+        GamepadInfo,
+    };
+
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, bevy::input::InputPlugin))
+            .insert_resource(Keymap::default())
+            .add_plugins(ActionsPlugin);
+        app.world.send_event(
+            GamepadEvent::Connection(
+                GamepadConnectionEvent::new(
+                    Gamepad::new(0),
+                    GamepadConnection::Connected(GamepadInfo { name: "test pad".to_string() })
+                )
+            )
+        );
+        app.update();
+        app
+    }
+
+    fn button(app: &mut App, button: GamepadButtonType, value: f32) {
+        app.world.send_event(GamepadEvent::Button(GamepadButtonChangedEvent::new(Gamepad::new(0), button, value)));
+        app.update();
+    }
+
+    fn stick(app: &mut App, axis: GamepadAxisType, value: f32) {
+        app.world.send_event(GamepadEvent::Axis(GamepadAxisChangedEvent::new(Gamepad::new(0), axis, value)));
+        app.update();
+    }
+
+    fn actions(app: &App) -> &ActionState {
+        app.world.resource::<ActionState>()
+    }
+
+    #[test]
+    fn gamepad_button_drops() {
+        let mut app = app();
+        button(&mut app, GamepadButtonType::South, 1.0);
+        assert!(actions(&app).just_pressed(GameAction::Drop));
+
+        app.update();
+        assert!(actions(&app).pressed(GameAction::Drop));
+        assert!(!actions(&app).just_pressed(GameAction::Drop));
+
+        button(&mut app, GamepadButtonType::South, 0.0);
+        assert!(actions(&app).just_released(GameAction::Drop));
+    }
+
+    #[test]
+    fn sticks_aim_and_pan() {
+        let mut app = app();
+        stick(&mut app, GamepadAxisType::LeftStickX, -1.0);
+        assert_eq!(actions(&app).axis(GameAction::AimLeft, GameAction::AimRight), -1.0);
+
+        stick(&mut app, GamepadAxisType::RightStickY, 0.6);
+        let up = actions(&app).axis(GameAction::CameraPanDown, GameAction::CameraPanUp);
+        assert!(up > 0.0 && up < 1.0);
+
+        // inside the deadzone
+        stick(&mut app, GamepadAxisType::LeftStickX, 0.1);
+        assert!(!actions(&app).pressed(GameAction::AimRight));
+        assert!(actions(&app).just_released(GameAction::AimLeft));
+    }
+
+    #[test]
+    fn rebound_button() {
+        let mut app = app();
+        let mut keymap = Keymap::default();
+        keymap.bindings.insert(GameAction::Drop, vec![Binding::Pad(GamepadButtonType::East)]);
+        app.insert_resource(keymap);
+
+        button(&mut app, GamepadButtonType::South, 1.0);
+        assert!(!actions(&app).pressed(GameAction::Drop));
+        button(&mut app, GamepadButtonType::East, 1.0);
+        assert!(actions(&app).just_pressed(GameAction::Drop));
+    }
+
+    /// Whether an action only does something in drop mode, only outside it, or either way
+    fn drop_mode(action: GameAction) -> Option<bool> {
+        use GameAction as A;
+        match action {
+            A::Drop | A::AimLeft | A::AimRight | A::SwapHold => Some(true),
+            A::Select | A::AddToSelection | A::TakeControl => Some(false),
+            A::CameraPanLeft | A::CameraPanRight | A::ZoomOut => Some(false),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn default_bindings_dont_clash() {
+        let keymap = Keymap::default();
+        for (a, a_bindings) in keymap.bindings.iter() {
+            for (b, b_bindings) in keymap.bindings.range(*a..).skip(1) {
+                if let Some(shared) = a_bindings.iter().find(|binding| b_bindings.contains(binding)) {
+                    let apart = matches!((drop_mode(*a), drop_mode(*b)), (Some(x), Some(y)) if x != y);
+                    assert!(apart, "{:?} and {:?} are both on {}", a, b, shared);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! A suika-style merge game on bevy and rapier.
+//!
+//! Add [`YukiPlugins`] to an app for the whole game, or [`YukiPlugins::headless`] for the
+//! simulation without a window. To embed the merge simulation in an app that already sets up
+//! rendering and rapier, disable the setup and controls plugins:
+//!
+//! ```ignore
+//! app.add_plugins(
+//!     YukiPlugins::default()
+//!         .build()
+//!         .disable::<yuki::setup::SetupPlugin>()
+//!         .disable::<yuki::controls::ControlsPlugin>()
+//! );
+//! ```
+//!
+//! The score, queue, menu and debug windows are only added when the host adds bevy_egui's `EguiPlugin`
+//! before `YukiPlugins`. Without it the game skips the main menu and starts playing straight away.
+//!
+//! Each plugin takes its settings as a config struct, inserted as a resource of the same type.
+//! [`YukiPlugins::from_config`] fills them from a [`YukiConfig`] file and keeps them in step with it.
+
+pub mod setup;
+pub mod prelude;
+pub mod controls;
+pub mod config;
+pub mod logic;
+pub mod game;
+pub mod replay;
+pub mod debug;
+pub mod perf;
+pub mod input;
+
+use bevy::app::{ PluginGroup, PluginGroupBuilder };
+
+pub use crate::config::{
+    GameConfig,
+    LogicConfig,
+    SetupConfig,
+    ControlsConfig,
+    YukiConfig,
+    ConfigSource,
+};
+
+/// Every yuki plugin, configured up front.
+///
+/// - [`setup::SetupPlugin`] (or [`setup::HeadlessPlugin`]): bevy, rapier, egui and the camera
+/// - [`game::GamePlugin`]: the container, game state and danger line
+/// - [`logic::LogicPlugin`]: tiers, dropping, merging and scoring
+/// - [`controls::ControlsPlugin`]: camera and mouse input, left out when headless
+/// - [`input::ActionsPlugin`]: the keymap from keys, mouse and gamepads to game actions, left out when headless
+/// - [`debug::DebugPlugin`]: physics pause, stepping and time scale, left out when headless
+/// - [`perf::PerfPlugin`]: the Stats window, left out when headless
+/// - [`replay::ReplayPlugin`]: recording and playback of sessions
+/// - [`config::ConfigPlugin`]: hot reloading of the config file, only when built with from_config
+#[derive(Default, Clone)]
+pub struct YukiPlugins {
+    pub game: GameConfig,
+    pub logic: LogicConfig,
+    pub setup: SetupConfig,
+    pub controls: ControlsConfig,
+    /// Run without a window or renderer, stepping physics at SetupConfig::fixed_dt
+    pub headless: bool,
+    /// File to watch for config changes
+    pub source: Option<ConfigSource>,
+    pub replay: replay::ReplayMode,
+}
+
+impl YukiPlugins {
+    pub fn headless() -> Self {
+        Self { headless: true, ..Default::default() }
+    }
+
+    /// Loads the config from `source` and watches it for changes while running
+    pub fn from_config(source: ConfigSource) -> Result<Self, String> {
+        let config = source.load()?;
+        Ok(Self { source: Some(source), ..Self::with_config(config) })
+    }
+
+    /// Uses `config` as is, without watching a file
+    pub fn with_config(config: YukiConfig) -> Self {
+        Self {
+            game: config.game,
+            logic: config.logic,
+            setup: config.setup,
+            controls: config.controls,
+            ..Default::default()
+        }
+    }
+
+    fn config(&self) -> YukiConfig {
+        YukiConfig {
+            game: self.game.clone(),
+            logic: self.logic.clone(),
+            setup: self.setup.clone(),
+            controls: self.controls.clone(),
+        }
+    }
+}
+
+impl PluginGroup for YukiPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        let mut group = PluginGroupBuilder::start::<Self>();
+        if let Some(source) = self.source.clone() {
+            group = group.add(config::ConfigPlugin { source, config: self.config() });
+        }
+        let group = if self.headless {
+            group.add(setup::HeadlessPlugin { config: self.setup })
+        } else {
+            group.add(setup::SetupPlugin { config: self.setup })
+        };
+        let group = group
+            .add(game::GamePlugin { config: self.game })
+            .add(logic::LogicPlugin { config: self.logic })
+            .add(replay::ReplayPlugin { mode: self.replay });
+        if self.headless {
+            group
+        } else {
+            group
+                .add(controls::ControlsPlugin { config: self.controls })
+                .add(input::ActionsPlugin)
+                .add(debug::DebugPlugin)
+                .add(perf::PerfPlugin)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::{ logic::{ body::Body, hud::SelectedPos }, LogicConfig, YukiPlugins };
+
+    /// Drops a piece every second along the container and records where every body ended up
+    fn layout(seed: u64) -> Vec<(usize, Vec3, Quat)> {
+        let mut app = App::new();
+        app.add_plugins(YukiPlugins {
+            logic: LogicConfig { seed: Some(seed), ..default() },
+            ..YukiPlugins::headless()
+        });
+        app.finish();
+        app.cleanup();
+
+        for frame in 0..600 {
+            if frame % 60 == 0 {
+                let x = ((frame / 60) as f32 - 5.0) * 40.0;
+                app.world.resource_mut::<SelectedPos>().0 = Some(Vec2::new(x, 300.0));
+            }
+            app.update();
+        }
+
+        let mut bodies = app.world
+            .query::<(Entity, &Body, &Transform)>()
+            .iter(&app.world)
+            .map(|(e, body, tf)| (e, (body.tier, tf.translation, tf.rotation)))
+            .collect::<Vec<_>>();
+        bodies.sort_by_key(|(e, _)| *e);
+        bodies
+            .into_iter()
+            .map(|(_, b)| b)
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_layout() {
+        let first = layout(1234);
+        assert!(first.len() > 1);
+        assert_eq!(first, layout(1234));
+    }
+}
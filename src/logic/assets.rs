@@ -2,56 +2,115 @@ use bevy::sprite::Mesh2dHandle;
 
 use crate::{ prelude::* };
 
+use super::tiers::TierTable;
+
 pub struct AssetPlugin;
 
+/// Colors tiers can use by name, each also gets an inverted "A" version for the face
+pub const NAMED_COLORS: [(&str, Color); 15] = [
+    ("WHITE", Color::WHITE),
+    ("BLACK", Color::BLACK),
+    ("RED", Color::RED),
+    ("BLUE", Color::BLUE),
+    ("GREEN", Color::GREEN),
+    ("YELLOW", Color::YELLOW),
+    ("ORANGE", Color::ORANGE),
+    ("PURPLE", Color::PURPLE),
+    ("CYAN", Color::CYAN),
+    ("PINK", Color::PINK),
+    ("TEAL", Color::TEAL),
+    ("LIME", Color::LIME_GREEN),
+    ("MAROON", Color::MAROON),
+    ("NAVY", Color::NAVY),
+    ("SILVER", Color::SILVER),
+];
+
 impl Plugin for AssetPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<GeneratedAssets>().add_systems(Startup, init_assets);
+        let path = app.world.resource::<LogicConfig>().tiers_path.clone();
+        let tiers = TierTable::load(&path).unwrap_or_else(|e| {
+            warn!("Using the built-in tier table, {}", e);
+            TierTable::default()
+        });
+        app.insert_resource(tiers)
+            .init_resource::<GeneratedAssets>()
+            .add_systems(Startup, init_assets);
     }
 }
 
+/// Meshes and materials for everything spawned at runtime.
+/// Without a renderer the meshes stay empty and colors have no material.
 #[derive(Resource, Default)]
 pub struct GeneratedAssets {
     pub meshes: HashMap<String, Mesh2dHandle>,
-    pub colors: HashMap<String, (Color, Handle<ColorMaterial>)>,
+    /// Circle meshes indexed by tier
+    pub tier_meshes: Vec<Mesh2dHandle>,
+    /// Slightly larger circles drawn behind each tier as its outline
+    pub tier_outlines: Vec<Mesh2dHandle>,
+    pub colors: HashMap<String, (Color, Option<Handle<ColorMaterial>>)>,
+}
+
+impl GeneratedAssets {
+    pub fn rendered(&self) -> bool {
+        !self.tier_meshes.is_empty()
+    }
+
+    pub fn color(&self, name: &str) -> Color {
+        self.colors.get(name).map_or(Color::WHITE, |(color, _)| *color)
+    }
+
+    pub fn material(&self, name: &str) -> Option<Handle<ColorMaterial>> {
+        self.colors.get(name).and_then(|(_, material)| material.clone())
+    }
+
+    /// Mesh and material for a body of `tier` in `color`, None when running headless
+    pub fn tier_render(&self, tier: usize, color: &str) -> Option<(Mesh2dHandle, Handle<ColorMaterial>)> {
+        Some((self.tier_meshes.get(tier)?.clone(), self.material(color)?))
+    }
 }
 
 pub fn init_assets(
     mut ga: ResMut<GeneratedAssets>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    mut materials: Option<ResMut<Assets<ColorMaterial>>>,
+    tiers: Res<TierTable>,
+    config: Res<GameConfig>
 ) {
+    if let Some(meshes) = meshes {
+        init_meshes(&mut ga, meshes, &tiers, &config);
+    }
 
-    for size in 0..15 {
-        let powed = (size + 1) as f32 * 2.0;
-        let mesh = meshes.add(
-            Mesh::from(shape::Circle { radius: powed * RADIUS, ..Default::default() })
-        );
-        ga.meshes.insert((powed).to_string(), mesh.into());
-    }
-    ga.meshes.insert("WALL".to_string(), meshes.add(Mesh::from(shape::Quad::new(Vec2::new(wall_half_x * 2.0, wall_half_y * 2.0)))).into());
-    ga.meshes.insert("FLOOR".to_string(), meshes.add(Mesh::from(shape::Quad::new(Vec2::new(floor_half_x * 2.0, floor_half_y * 2.0)))).into());
-    
-
-    let colors = vec![
-        ("WHITE".to_string(), Color::WHITE),
-        ("BLACK".to_string(), Color::BLACK),
-        ("RED".to_string(), Color::RED),
-        ("BLUE".to_string(), Color::BLUE),
-        ("GREEN".to_string(), Color::GREEN),
-        ("YELLOW".to_string(), Color::YELLOW),
-        ("ORANGE".to_string(), Color::ORANGE),
-        ("PURPLE".to_string(), Color::PURPLE)
-    ];
-
-    for (title, color) in colors {
-        let material = materials.add(color.into());
-        ga.colors.insert(title.clone(), (color, material));
+
+    for (title, color) in NAMED_COLORS {
+        let material = materials.as_mut().map(|m| m.add(color.into()));
+        ga.colors.insert(title.to_string(), (color, material));
         // for each color, generate an "A{color}" color which is the color with each channel as 1-channel value
         // for example, ARED is (1-RED.r, etc)
 
         let a_color = Color::rgb(1.0 - color.r(), 1.0 - color.g(), 1.0 - color.b());
-        let a_material = materials.add(a_color.into());
+        let a_material = materials.as_mut().map(|m| m.add(a_color.into()));
         ga.colors.insert(format!("A{}", title), (a_color, a_material));
     }
 }
+
+fn init_meshes(
+    ga: &mut GeneratedAssets,
+    mut meshes: ResMut<Assets<Mesh>>,
+    tiers: &TierTable,
+    config: &GameConfig
+) {
+    for tier in tiers.tiers.iter() {
+        let mesh = meshes.add(
+            Mesh::from(shape::Circle { radius: config.pixels(tier.radius), ..Default::default() })
+        );
+        ga.tier_meshes.push(mesh.into());
+        let outline = meshes.add(
+            Mesh::from(shape::Circle { radius: config.pixels(tier.radius) + OUTLINE_WIDTH, ..Default::default() })
+        );
+        ga.tier_outlines.push(outline.into());
+    }
+    // unit circle, scaled per tier for the face
+    ga.meshes.insert("EYEBALL".to_string(), meshes.add(Mesh::from(shape::Circle::new(1.0))).into());
+    ga.meshes.insert("WALL".to_string(), meshes.add(Mesh::from(shape::Quad::new(Vec2::new(config.wall_half_x * 2.0, config.wall_half_y * 2.0)))).into());
+    ga.meshes.insert("FLOOR".to_string(), meshes.add(Mesh::from(shape::Quad::new(Vec2::new(config.floor_half_x * 2.0, config.floor_half_y * 2.0)))).into());
+}
@@ -6,13 +6,14 @@ use crate::prelude::*;
 
 #[derive(Component, Reflect)]
 pub struct Body {
+    pub tier: usize,
     pub color: Color,
     pub radius: f32,
 }
 
 impl Body {
-    pub fn new(radius: f32, color: Color) -> Self {
-        Self { radius, color }
+    pub fn new(tier: usize, radius: f32, color: Color) -> Self {
+        Self { tier, radius, color }
     }
 
     pub fn lin_margin(&self) -> f32 {
@@ -43,26 +44,28 @@ impl BodyBundle {
         Self { body, pb }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn spawn(
+        tier: usize,
         radius: f32,
         color: Color,
         pos: Vec2,
         dir: Vec2,
-        mesh: Mesh2dHandle,
-        material: Handle<ColorMaterial>,
-        commands: &mut Commands
+        render: Option<(Mesh2dHandle, Handle<ColorMaterial>)>,
+        commands: &mut Commands,
+        config: &GameConfig
     ) -> Entity {
         let mut tf = Transform::from_translation(pos.extend(VISIBLE_Z));
         tf.rotate_z(Vec2::Y.angle_between(dir));
 
-        let pb = PhysicsBody::new(Collider::ball(radius * RADIUS), Render {
-            mesh,
-            material,
-            transform: tf,
-            ..Default::default()
-        });
-        let body = Body::new(radius, color);
+        let pb = PhysicsBody::new(Collider::ball(config.pixels(radius)), tf, config);
+        let body = Body::new(tier, radius, color);
 
-        commands.spawn(BodyBundle::new(body, pb)).id()
+        let mut ec = commands.spawn(BodyBundle::new(body, pb));
+        // headless bodies have nothing to draw
+        if let Some((mesh, material)) = render {
+            ec.insert(make_render(mesh, material, tf));
+        }
+        ec.id()
     }
 }
@@ -0,0 +1,121 @@
+use crate::prelude::*;
+
+use super::merge::resolve_merges;
+
+pub struct BurstPlugin;
+
+impl Plugin for BurstPlugin {
+    fn build(&self, app: &mut App) {
+        // the same frame as the merge, before the pair is despawned
+        app.add_event::<MaxTierMerged>().add_systems(Update, burst.after(resolve_merges));
+    }
+}
+
+/// Two max-tier bodies met and vanished instead of merging
+#[derive(Event, Clone, Debug)]
+pub struct MaxTierMerged {
+    pub pos: Vec2,
+    pub tier: usize,
+    pub bodies: [Entity; 2],
+    pub points: u64,
+}
+
+/// Pushes every dynamic body near a max-tier merge away from it,
+/// strongest at the center and fading out at LogicConfig::burst_radius
+fn burst(
+    mut merged: EventReader<MaxTierMerged>,
+    rc: Res<RapierContext>,
+    config: Res<LogicConfig>,
+    mut bodies: Query<(&Transform, &mut ExternalImpulse, Option<&ReadMassProperties>)>
+) {
+    for ev in merged.iter() {
+        let mut hit = Vec::new();
+        let filter = QueryFilter::only_dynamic();
+        rc.intersections_with_shape(ev.pos, 0.0, &Collider::ball(config.burst_radius), filter, |e| {
+            if !ev.bodies.contains(&e) {
+                hit.push(e);
+            }
+            true
+        });
+
+        for e in hit {
+            if let Ok((tf, mut impulse, mass)) = bodies.get_mut(e) {
+                let offset = tf.translation.truncate() - ev.pos;
+                let falloff = (1.0 - offset.length() / config.burst_radius).max(0.0);
+                let mass = mass.map_or(1.0, |m| m.0.mass);
+                impulse.impulse += offset.normalize_or_zero() * config.burst_speed * falloff * mass;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ game::snow::Snow, logic::{ assets::GeneratedAssets, tiers::TierTable }, YukiPlugins };
+
+    use super::*;
+
+    /// What the burst did, read right after it runs since rapier clears impulses once applied
+    #[derive(Resource, Default)]
+    struct Seen {
+        merged: usize,
+        outward: Vec<f32>,
+    }
+
+    #[derive(Resource)]
+    struct Neighbor(Entity);
+
+    fn spawn_bodies(
+        mut commands: Commands,
+        assets: Res<GeneratedAssets>,
+        tiers: Res<TierTable>,
+        game: Res<GameConfig>
+    ) {
+        // two overlapping top-tier bodies on the floor and a flake beside them
+        for x in [-25.0, 25.0] {
+            Snow::spawn(Vec2::new(x, -450.0), Vec2::Y, tiers.max_tier(), &mut commands, &assets, &tiers, &game);
+        }
+        let neighbor = Snow::spawn(Vec2::new(100.0, -480.0), Vec2::Y, 0, &mut commands, &assets, &tiers, &game);
+        commands.insert_resource(Neighbor(neighbor));
+    }
+
+    fn watch(
+        mut merged: EventReader<MaxTierMerged>,
+        neighbor: Option<Res<Neighbor>>,
+        bodies: Query<(&Transform, &ExternalImpulse)>,
+        mut seen: ResMut<Seen>
+    ) {
+        for ev in merged.iter() {
+            seen.merged += 1;
+            let Some(Ok((tf, impulse))) = neighbor.as_ref().map(|n| bodies.get(n.0)) else {
+                continue;
+            };
+            let away = tf.translation.truncate() - ev.pos;
+            seen.outward.push(impulse.impulse.dot(away));
+        }
+    }
+
+    #[test]
+    fn top_tiers_burst_outwards() {
+        let mut app = App::new();
+        app.add_plugins(YukiPlugins {
+            // small enough that two top tiers fit in the container
+            game: GameConfig { radius: 1.0, ..default() },
+            logic: LogicConfig { seed: Some(1), ..default() },
+            ..YukiPlugins::headless()
+        });
+        app.finish();
+        app.cleanup();
+        app.init_resource::<Seen>()
+            .add_systems(Update, (spawn_bodies.run_if(run_once()), watch.after(burst)));
+
+        for _ in 0..10 {
+            app.update();
+        }
+
+        let seen = app.world.resource::<Seen>();
+        assert_eq!(seen.merged, 1);
+        assert_eq!(seen.outward.len(), 1);
+        assert!(seen.outward[0] > 0.0);
+    }
+}
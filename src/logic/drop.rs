@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use crate::{
+    prelude::*,
+    controls::cursor_world_pos,
+    game::GameState,
+    input::{ ActionState, GameAction },
+    replay::replaying,
+};
+
+use super::{ hud::SelectedPos, queue::PieceQueue, tiers::TierTable };
+
+pub struct DropPlugin;
+
+impl Plugin for DropPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Dropper>()
+            .add_systems(Update, apply_drop_config.run_if(resource_changed::<LogicConfig>()));
+        // headless runs have no player, drops arrive through SelectedPos directly
+        if !is_headless(app) {
+            app.add_systems(
+                Update,
+                (
+                    toggle_drop_mode.run_if(in_state(GameState::Playing)),
+                    (
+                        aim_drop,
+                        release_drop.after(aim_drop).run_if(not(replaying)),
+                        draw_held_piece.after(aim_drop),
+                    )
+                        .run_if(drop_mode_enabled)
+                        .run_if(in_state(GameState::Playing)),
+                )
+            );
+        }
+    }
+}
+
+/// The piece held at the top of the container, waiting to be dropped
+#[derive(Resource)]
+pub struct Dropper {
+    pub enabled: bool,
+    pub x: f32,
+    pub y: f32,
+    pub cooldown: Timer,
+}
+
+impl FromWorld for Dropper {
+    fn from_world(world: &mut World) -> Self {
+        let config = world.resource::<LogicConfig>();
+        let mut cooldown = Timer::from_seconds(config.drop_cooldown, TimerMode::Once);
+        // the first drop shouldn't have to wait
+        cooldown.tick(Duration::from_secs_f32(config.drop_cooldown));
+        Self {
+            enabled: true,
+            x: 0.0,
+            y: config.drop_y,
+            cooldown,
+        }
+    }
+}
+
+impl Dropper {
+    /// Keeps a held piece of `radius` pixels between the inner faces of the walls
+    pub fn clamp_x(x: f32, radius: f32, config: &GameConfig) -> f32 {
+        let inner = config.inner_half_x() - radius;
+        x.clamp(-inner.max(0.0), inner.max(0.0))
+    }
+
+    pub fn drop_pos(&self, radius: f32, config: &GameConfig) -> Vec2 {
+        Vec2::new(Dropper::clamp_x(self.x, radius, config), self.y)
+    }
+
+    /// Restarts the cooldown after a drop
+    pub fn next(&mut self) {
+        self.cooldown.reset();
+    }
+}
+
+fn apply_drop_config(config: Res<LogicConfig>, mut dropper: ResMut<Dropper>) {
+    dropper.y = config.drop_y;
+    dropper.cooldown.set_duration(Duration::from_secs_f32(config.drop_cooldown));
+}
+
+pub fn drop_mode_enabled(dropper: Res<Dropper>) -> bool {
+    dropper.enabled
+}
+
+/// Tab switches between dropping and free camera movement
+fn toggle_drop_mode(actions: Res<ActionState>, mut dropper: ResMut<Dropper>) {
+    if actions.just_pressed(GameAction::ToggleDropMode) {
+        dropper.enabled = !dropper.enabled;
+    }
+}
+
+/// The held piece follows the cursor when it moves, otherwise A/D or a stick nudge it
+fn aim_drop(
+    mut dropper: ResMut<Dropper>,
+    queue: Res<PieceQueue>,
+    tiers: Res<TierTable>,
+    actions: Res<ActionState>,
+    windows: Query<&Window>,
+    camera: Query<(&Transform, &OrthographicProjection), With<Camera>>,
+    mut last_cursor: Local<Option<Vec2>>,
+    time: Res<Time>,
+    game: Res<GameConfig>,
+    logic: Res<LogicConfig>
+) {
+    dropper.cooldown.tick(time.delta());
+
+    if let (Ok(window), Ok((camera_tf, proj))) = (windows.get_single(), camera.get_single()) {
+        let cursor = cursor_world_pos(window, camera_tf, proj);
+        if let Some(pos) = cursor {
+            if *last_cursor != Some(pos) {
+                dropper.x = pos.x;
+            }
+        }
+        *last_cursor = cursor;
+    }
+
+    let dx = actions.axis(GameAction::AimLeft, GameAction::AimRight);
+    dropper.x += dx * logic.drop_aim_speed * time.delta_seconds();
+    let radius = game.pixels(tiers.tiers[queue.current].radius);
+    dropper.x = Dropper::clamp_x(dropper.x, radius, &game);
+}
+
+/// Click or space releases the held piece into SelectedPos once the cooldown is over
+pub fn release_drop(
+    dropper: Res<Dropper>,
+    queue: Res<PieceQueue>,
+    tiers: Res<TierTable>,
+    mut sp: ResMut<SelectedPos>,
+    actions: Res<ActionState>,
+    game: Res<GameConfig>
+) {
+    if actions.just_pressed(GameAction::Drop) && dropper.cooldown.finished() {
+        let radius = game.pixels(tiers.tiers[queue.current].radius);
+        sp.0 = Some(dropper.drop_pos(radius, &game));
+    }
+}
+
+fn draw_held_piece(
+    mut gizmos: Gizmos,
+    dropper: Res<Dropper>,
+    queue: Res<PieceQueue>,
+    tiers: Res<TierTable>,
+    game: Res<GameConfig>
+) {
+    let radius = game.pixels(tiers.tiers[queue.current].radius);
+    let pos = dropper.drop_pos(radius, &game);
+    let color = if dropper.cooldown.finished() { Color::WHITE } else { Color::GRAY };
+    gizmos.circle_2d(pos, radius, color);
+    gizmos.line_2d(pos, Vec2::new(pos.x, -game.wall_half_y), Color::rgba(1.0, 1.0, 1.0, 0.2));
+}
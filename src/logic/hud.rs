@@ -1,6 +1,6 @@
 use bevy_egui::{ egui, EguiContexts };
 
-use crate::{ prelude::*};
+use crate::{ prelude::*, replay::Recorder };
 
 pub struct HudPlugin;
 
@@ -8,8 +8,10 @@ impl Plugin for HudPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<CameraFollows>()
             .init_resource::<SelectedPos>()
-            .init_resource::<ActiveControl>()
-            .add_systems(Update, display);
+            .init_resource::<ActiveControl>();
+        if has_egui(app) {
+            app.add_systems(Update, display);
+        }
     }
 }
 
@@ -24,19 +26,19 @@ pub struct SelectedPos(pub Option<Vec2>);
 
 pub fn display(
     mut contexts: EguiContexts,
-    mut display: Local<HashSet<String>>,
     mut camera_follows: ResMut<CameraFollows>,
-    keys: Res<Input<KeyCode>>,
-    sp: Res<SelectedPos>,
     mut ac: ResMut<ActiveControl>,
-    mut gizmos: Gizmos
+    recorder: Option<Res<Recorder>>
 ) {
     // let mut agent = None;
 
     egui::Window::new("Active Control").default_open(false).show(contexts.ctx_mut(), |ui| {
         match ac.0 {
             None => {
-                ui.label("Select an agent");
+                ui.label("Ctrl-click a body to control it with the arrow keys");
+                if recorder.is_some() {
+                    ui.label("Driving is off while recording");
+                }
             }
             Some(e) => {
                 ui.label(format!("Selected agent {:?}", e));
@@ -0,0 +1,320 @@
+use crate::{ prelude::*, game::{ snow::Snow, GameState }, replay::SimTime };
+
+use super::{
+    spawning::clear_of_walls,
+    body::Body,
+    assets::GeneratedAssets,
+    score::Score,
+    tiers::TierTable,
+    burst::MaxTierMerged,
+};
+
+pub struct MergePlugin;
+
+impl Plugin for MergePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MergeCandidates>()
+            // contacts are still collected while paused so none are lost, they merge once play resumes
+            .add_systems(Update, (
+                collect_merges,
+                resolve_merges.after(collect_merges).run_if(in_state(GameState::Playing)),
+            ))
+            .add_systems(PostUpdate, despawn_claimed.before(PhysicsSet::SyncBackend));
+    }
+}
+
+/// Equal-tier pairs that touched this frame, waiting to be resolved
+#[derive(Resource, Default)]
+pub struct MergeCandidates(pub Vec<(Entity, Entity)>);
+
+/// A body that has been used up by a merge and is despawned at the end of the frame.
+/// `into` is the body it merged into, or None when it burst.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Claimed {
+    pub into: Option<Entity>,
+}
+
+/// Picks the pairs that get to merge, so that each body is claimed at most once.
+/// Pairs are ordered by entity so the same contacts always resolve the same way.
+pub fn resolve_pairs(pairs: impl IntoIterator<Item = (Entity, Entity)>) -> Vec<(Entity, Entity)> {
+    let mut pairs = pairs
+        .into_iter()
+        .filter(|(a, b)| a != b)
+        .map(|(a, b)| if a < b { (a, b) } else { (b, a) })
+        .collect::<Vec<_>>();
+    pairs.sort();
+    pairs.dedup();
+
+    let mut claimed = HashSet::new();
+    pairs
+        .into_iter()
+        .filter(|(a, b)| {
+            if claimed.contains(a) || claimed.contains(b) {
+                false
+            } else {
+                claimed.insert(*a);
+                claimed.insert(*b);
+                true
+            }
+        })
+        .collect()
+}
+
+/// Gathers equal-tier contacts without acting on them
+pub fn collect_merges(
+    mut cev: EventReader<CollisionEvent>,
+    mut candidates: ResMut<MergeCandidates>,
+    snow: Query<&Body, Without<Claimed>>
+) {
+    for ev in cev.iter() {
+        match ev {
+            CollisionEvent::Started(e1, e2, _) => {
+                if let (Ok(b1), Ok(b2)) = (snow.get(*e1), snow.get(*e2)) {
+                    if b1.tier == b2.tier {
+                        candidates.0.push((*e1, *e2));
+                    }
+                }
+            }
+            CollisionEvent::Stopped(_, _, _) => {
+                // do nothing
+            }
+        }
+    }
+}
+
+/// Merges every claimed pair into the next tier, or bursts it at the top of the ladder.
+/// Bodies that lost a claim stay put, any merge they still have comes from the next contact.
+/// The merged body carries on with the pair's momentum and orientation.
+#[allow(clippy::too_many_arguments)]
+pub fn resolve_merges(
+    mut candidates: ResMut<MergeCandidates>,
+    mut commands: Commands,
+    snow: Query<(&Body, &Transform, &Velocity, Option<&ReadMassProperties>), Without<Claimed>>,
+    rc: Res<RapierContext>,
+    assets: Res<GeneratedAssets>,
+    tiers: Res<TierTable>,
+    mut score: ResMut<Score>,
+    sim_time: Res<SimTime>,
+    mut max_merged: EventWriter<MaxTierMerged>,
+    game: Res<GameConfig>,
+    logic: Res<LogicConfig>,
+    setup: Res<SetupConfig>
+) {
+    for (e1, e2) in resolve_pairs(candidates.0.drain(..)) {
+        let Ok((b1, tf1, v1, m1)) = snow.get(e1) else {
+            warn!("E1 not found in merge");
+            continue;
+        };
+        let Ok((b2, tf2, v2, m2)) = snow.get(e2) else {
+            warn!("E2 not found in merge");
+            continue;
+        };
+
+        let pos = (tf1.translation.truncate() + tf2.translation.truncate()) / 2.0;
+
+        let into = if b1.tier >= tiers.max_tier() {
+            // nothing to merge into, the pair bursts instead
+            let points = score.record_merge(tiers.max_merge_bonus, sim_time.0, &logic);
+            max_merged.send(MaxTierMerged {
+                pos,
+                tier: b1.tier,
+                bodies: [e1, e2],
+                points,
+            });
+            None
+        } else {
+            let tier = b1.tier + 1;
+            let m1 = mass(b1, m1, &tiers, &game, &setup);
+            let m2 = mass(b2, m2, &tiers, &game, &setup);
+            let vel = Velocity {
+                linvel: (v1.linvel * m1 + v2.linvel * m2) / (m1 + m2),
+                angvel: (v1.angvel + v2.angvel) / 2.0,
+            };
+            let dir = (tf1.rotation.slerp(tf2.rotation, 0.5) * Vec3::Y).truncate();
+            let radius = game.pixels(tiers.tiers[tier].radius);
+            let pos = clear_of_walls(radius, pos, game.radius, logic.spawn_nudge_steps, &rc);
+
+            let merged = Snow::spawn(pos, dir, tier, &mut commands, &assets, &tiers, &game);
+            commands.entity(merged).insert(vel);
+            let points = score.record_merge(tiers.tiers[tier].score, sim_time.0, &logic);
+            debug!("merged into {:?} for {} points", merged, points);
+            Some(merged)
+        };
+
+        commands.entity(e1).insert(Claimed { into });
+        commands.entity(e2).insert(Claimed { into });
+    }
+}
+
+/// Rapier's mass once it has been computed, otherwise the same figure worked out from the tier
+fn mass(
+    body: &Body,
+    read: Option<&ReadMassProperties>,
+    tiers: &TierTable,
+    game: &GameConfig,
+    setup: &SetupConfig
+) -> f32 {
+    match read {
+        Some(read) if read.0.mass > 0.0 => read.0.mass,
+        _ => estimated_mass(body, tiers, game, setup),
+    }
+}
+
+/// The mass rapier gives a ball collider of the body's size: density times its area in meters
+pub fn estimated_mass(body: &Body, tiers: &TierTable, game: &GameConfig, setup: &SetupConfig) -> f32 {
+    let density = tiers.get(body.tier).map_or(1.0, |t| t.density);
+    let radius = game.pixels(body.radius) / setup.pixels_per_meter;
+    density * std::f32::consts::PI * radius.powi(2)
+}
+
+pub fn despawn_claimed(mut commands: Commands, claimed: Query<Entity, With<Claimed>>) {
+    for entity in claimed.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let logic = LogicConfig::default();
+        let tiers = TierTable::default();
+        // no meshes, the same as running headless
+        let assets = GeneratedAssets::default();
+
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(State::new(GameState::Playing))
+            .add_state::<GameState>()
+            .add_event::<CollisionEvent>()
+            .add_event::<MaxTierMerged>()
+            .insert_resource(tiers)
+            .insert_resource(assets)
+            .insert_resource(logic)
+            .init_resource::<GameConfig>()
+            .init_resource::<SetupConfig>()
+            .init_resource::<Score>()
+            .init_resource::<SimTime>()
+            .init_resource::<RapierContext>()
+            .add_plugins(MergePlugin);
+        app
+    }
+
+    fn spawn(app: &mut App, tier: usize, x: f32) -> Entity {
+        app.world
+            .spawn((
+                Body::new(tier, 2.0, Color::WHITE),
+                Transform::from_xyz(x, 0.0, VISIBLE_Z),
+                Velocity::default(),
+            ))
+            .id()
+    }
+
+    fn touch(app: &mut App, a: Entity, b: Entity) {
+        app.world.send_event(CollisionEvent::Started(a, b, CollisionEventFlags::empty()));
+    }
+
+    fn tiers(app: &mut App) -> Vec<usize> {
+        let mut tiers = app.world
+            .query::<&Body>()
+            .iter(&app.world)
+            .map(|b| b.tier)
+            .collect::<Vec<_>>();
+        tiers.sort();
+        tiers
+    }
+
+    #[test]
+    fn triangle_merges_once() {
+        let mut app = app();
+        let a = spawn(&mut app, 0, 0.0);
+        let b = spawn(&mut app, 0, 1.0);
+        let c = spawn(&mut app, 0, 2.0);
+        touch(&mut app, a, b);
+        touch(&mut app, b, c);
+        touch(&mut app, c, a);
+        app.update();
+
+        assert_eq!(tiers(&mut app), vec![0, 1]);
+        assert_eq!(app.world.resource::<Score>().merges, 1);
+    }
+
+    #[test]
+    fn line_merges_in_pairs() {
+        let mut app = app();
+        let bodies = (0..4).map(|i| spawn(&mut app, 0, i as f32)).collect::<Vec<_>>();
+        for pair in bodies.windows(2) {
+            touch(&mut app, pair[1], pair[0]);
+        }
+        app.update();
+
+        assert_eq!(tiers(&mut app), vec![1, 1]);
+    }
+
+    #[test]
+    fn chain_cascades_on_later_frames() {
+        let mut app = app();
+        let bodies = (0..4).map(|i| spawn(&mut app, 0, i as f32)).collect::<Vec<_>>();
+        for pair in bodies.windows(2) {
+            touch(&mut app, pair[0], pair[1]);
+        }
+        app.update();
+
+        let merged = app.world
+            .query_filtered::<Entity, With<Snow>>()
+            .iter(&app.world)
+            .collect::<Vec<_>>();
+        assert_eq!(merged.len(), 2);
+        touch(&mut app, merged[0], merged[1]);
+        app.update();
+
+        assert_eq!(tiers(&mut app), vec![2]);
+        assert_eq!(app.world.resource::<Score>().merges, 3);
+    }
+
+    #[test]
+    fn merge_keeps_momentum() {
+        let mut app = app();
+        let a = spawn(&mut app, 0, 0.0);
+        let b = spawn(&mut app, 0, 1.0);
+        app.world.entity_mut(a).insert(Velocity { linvel: Vec2::new(100.0, 0.0), angvel: 1.0 });
+        app.world.entity_mut(b).insert(Velocity { linvel: Vec2::new(0.0, -50.0), angvel: 3.0 });
+        touch(&mut app, a, b);
+        app.update();
+
+        let vel = app.world
+            .query_filtered::<&Velocity, With<Snow>>()
+            .single(&app.world);
+        assert_eq!(vel.linvel, Vec2::new(50.0, -25.0));
+        assert_eq!(vel.angvel, 2.0);
+    }
+
+    #[test]
+    fn merge_weights_momentum_by_mass() {
+        let mut app = app();
+        let a = spawn(&mut app, 0, 0.0);
+        let b = spawn(&mut app, 0, 1.0);
+        // a has its mass from rapier already, b was dropped this frame and is estimated
+        let estimate = {
+            let world = &app.world;
+            estimated_mass(
+                world.get::<Body>(b).unwrap(),
+                world.resource::<TierTable>(),
+                world.resource::<GameConfig>(),
+                world.resource::<SetupConfig>()
+            )
+        };
+        let props = MassProperties { mass: estimate * 3.0, ..default() };
+        app.world
+            .entity_mut(a)
+            .insert((Velocity { linvel: Vec2::new(100.0, 0.0), angvel: 0.0 }, ReadMassProperties(props)));
+        touch(&mut app, a, b);
+        app.update();
+
+        let vel = app.world
+            .query_filtered::<&Velocity, With<Snow>>()
+            .single(&app.world);
+        assert!((vel.linvel - Vec2::new(75.0, 0.0)).length() < 1e-3);
+    }
+}
@@ -1,101 +1,55 @@
 use std::time::Duration;
 
 use bevy::time::Stopwatch;
-use rand::Rng;
 
-use crate::{prelude::*, game::snow::Snow};
+use crate::{prelude::*, game::{snow::Snow, GameState}};
 
-use self::{body::Body, assets::GeneratedAssets, hud::SelectedPos};
+use self::{body::Body, assets::GeneratedAssets, hud::SelectedPos, drop::Dropper, queue::PieceQueue, rng::GameRng, tiers::TierTable};
 
 pub mod hud;
 pub mod assets;
 pub mod spawning;
 pub mod body;
-
-pub struct LogicPlugin;
+pub mod score;
+pub mod drop;
+pub mod queue;
+pub mod tiers;
+pub mod burst;
+pub mod merge;
+pub mod rng;
+pub mod save;
+pub mod undo;
+
+#[derive(Default)]
+pub struct LogicPlugin {
+    pub config: LogicConfig,
+}
 
 impl Plugin for LogicPlugin {
     fn build(&self, app: &mut App) {
-        app.register_type::<Body>().add_plugins((hud::HudPlugin, assets::AssetPlugin)).add_systems(Update, (combine, spawn_on_click.after(crate::controls::mouse_selection)));
+        app.insert_resource(self.config.clone())
+            .init_resource::<rng::GameRng>()
+            .register_type::<Body>().add_plugins((hud::HudPlugin, assets::AssetPlugin, score::ScorePlugin, drop::DropPlugin, queue::QueuePlugin, burst::BurstPlugin, merge::MergePlugin, save::SavePlugin, undo::UndoPlugin)).add_systems(Update, spawn_on_click.after(drop::release_drop).run_if(in_state(GameState::Playing)));
     }
 }
 
 
+/// Spawns the held piece wherever a drop was released
 pub fn spawn_on_click(
     mut sp: ResMut<SelectedPos>,
     mut commands: Commands,
     assets: Res<GeneratedAssets>,
-    mut cooldown: Local<f32>,
-    time: Res<Time>,
+    mut dropper: ResMut<Dropper>,
+    mut queue: ResMut<PieceQueue>,
+    mut rng: ResMut<GameRng>,
+    tiers: Res<TierTable>,
+    config: Res<GameConfig>,
 ) {
+    if let Some(pos) = sp.0.take() {
+        let dir = Vec2::Y;
 
-    *cooldown += time.delta_seconds();
-
-    if let Some(pos) = sp.0 {
-
-        if *cooldown > 0.1 {
-            let dir = Vec2::Y;
-            let color = String::from("WHITE");
-
-            let size = rand::thread_rng().gen_range(1..5);
-            Snow::spawn(pos, dir, color, &mut commands, &assets, size as f32 * 2.0);
-    
-            sp.0 = None;
-            *cooldown = 0.0;
-            dbg!("spawn sent");
-        } else {
-            // dbg!("Spwan accepted, waiting on cooldown");
-        }
-
+        let tier = queue.advance(&mut *rng);
+        Snow::spawn(pos, dir, tier, &mut commands, &assets, &tiers, &config);
+        dropper.next();
     }
 }
-
-
-pub fn combine(
-    mut cev: EventReader<CollisionEvent>,
-    mut commands: Commands,
-    snow: Query<(&Body, &Transform)>,
-    assets: Res<GeneratedAssets>
-
-) {
-    for ev in cev.iter() {
-        match ev {
-            CollisionEvent::Started(e1, e2, _) => {
-                if let Ok((b1, tf1)) = snow.get(*e1) {
-                    if let Ok((b2, tf2)) = snow.get(*e2) {
-                        if b1.radius == b2.radius {
-
-                            let pos1 = tf1.translation.truncate();
-                            let pos2 = tf2.translation.truncate();
-
-                            let pos = (pos1 + pos2) / 2.0;
-
-                            if let Some(ec) = commands.get_entity(*e1) {
-                                ec.despawn_recursive();
-                            } else {
-                                warn!("Couldn't despawn e1c");
-                            }
-
-                            if let Some(ec) = commands.get_entity(*e2) {
-                                ec.despawn_recursive();
-                            } else {
-                                warn!("Couldn't despawn e1c");
-                            }
-
-                            let dir = Vec2::Y;
-                            let color = String::from("WHITE");
-                            Snow::spawn(pos, dir, color, &mut commands, &assets, b1.radius + 2.0);
-                        }
-                    } else {
-                        warn!("E2 not found in combine");
-                    }
-                } else {
-                    warn!("E1 not found in combine");
-                }
-            },
-            CollisionEvent::Stopped(_, _, _) => {
-                // do nothing
-            },
-        }
-    }
-}
\ No newline at end of file
@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+
+use bevy_egui::{ egui, EguiContexts };
+use rand::Rng;
+use serde::{ Deserialize, Serialize };
+
+use crate::{
+    prelude::*,
+    game::GameState,
+    input::{ ActionState, GameAction },
+    replay::{ replaying, ReplayPlayer },
+};
+
+use super::{ drop::drop_mode_enabled, rng::GameRng, tiers::TierTable };
+
+pub struct QueuePlugin;
+
+impl Plugin for QueuePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PieceQueue>()
+            .add_event::<SwapHold>()
+            .add_systems(Update, (
+                apply_hold.run_if(in_state(GameState::Playing)),
+                apply_preview_len.run_if(resource_changed::<LogicConfig>()),
+            ));
+        // headless runs have no player, holds arrive as SwapHold events directly
+        if !is_headless(app) {
+            app.add_systems(
+                Update,
+                hold_piece
+                    .before(apply_hold)
+                    .run_if(drop_mode_enabled)
+                    .run_if(not(replaying))
+                    .run_if(in_state(GameState::Playing))
+            );
+        }
+        if has_egui(app) {
+            app.add_systems(Update, queue_window);
+        }
+    }
+}
+
+/// Swaps the held piece with the hold slot
+#[derive(Event, Clone, Copy, Debug, Default)]
+pub struct SwapHold;
+
+/// Tiers of the held piece and the ones coming after it, generated ahead of time so players can plan
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct PieceQueue {
+    pub current: usize,
+    pub upcoming: VecDeque<usize>,
+    pub hold: Option<usize>,
+    /// The hold slot can only be used once per drop
+    pub hold_used: bool,
+    /// Pieces are drawn from tiers below this
+    drop_tiers: usize,
+}
+
+impl FromWorld for PieceQueue {
+    fn from_world(world: &mut World) -> Self {
+        let drop_tiers = world.resource::<TierTable>().drop_range();
+        let len = world.resource::<LogicConfig>().preview_len;
+        let mut rng = world.resource_mut::<GameRng>();
+        Self::new(len, drop_tiers, &mut *rng)
+    }
+}
+
+impl PieceQueue {
+    pub fn new(len: usize, drop_tiers: usize, rng: &mut impl Rng) -> Self {
+        Self {
+            current: PieceQueue::random_tier(drop_tiers, rng),
+            upcoming: (0..len).map(|_| PieceQueue::random_tier(drop_tiers, rng)).collect(),
+            hold: None,
+            hold_used: false,
+            drop_tiers,
+        }
+    }
+
+    /// Draws or drops upcoming pieces so `len` are shown
+    pub fn resize(&mut self, len: usize, rng: &mut impl Rng) {
+        while self.upcoming.len() < len {
+            self.upcoming.push_back(PieceQueue::random_tier(self.drop_tiers, rng));
+        }
+        self.upcoming.truncate(len);
+    }
+
+    /// The first queued tier `tiers` doesn't have, counting the highest one the queue still draws
+    pub fn missing_tier(&self, tiers: &TierTable) -> Option<usize> {
+        std::iter
+            ::once(self.current)
+            .chain(self.upcoming.iter().copied())
+            .chain(self.hold)
+            .chain(self.drop_tiers.checked_sub(1))
+            .find(|tier| tiers.get(*tier).is_none())
+    }
+
+    pub fn random_tier(drop_tiers: usize, rng: &mut impl Rng) -> usize {
+        rng.gen_range(0..drop_tiers)
+    }
+
+    fn pop_next(&mut self, rng: &mut impl Rng) -> usize {
+        self.upcoming.push_back(PieceQueue::random_tier(self.drop_tiers, rng));
+        self.upcoming.pop_front().unwrap_or(0)
+    }
+
+    /// Moves on to the next piece after a drop, returning the tier that was dropped
+    pub fn advance(&mut self, rng: &mut impl Rng) -> usize {
+        let dropped = self.current;
+        self.current = self.pop_next(rng);
+        self.hold_used = false;
+        dropped
+    }
+
+    /// Stashes the current piece, taking the held one (or the next in line) instead
+    pub fn swap_hold(&mut self, rng: &mut impl Rng) -> bool {
+        if self.hold_used {
+            return false;
+        }
+        let held = self.hold.replace(self.current);
+        self.current = match held {
+            Some(tier) => tier,
+            None => self.pop_next(rng),
+        };
+        self.hold_used = true;
+        true
+    }
+}
+
+/// Q swaps the held piece with the hold slot
+fn hold_piece(actions: Res<ActionState>, mut swap: EventWriter<SwapHold>) {
+    if actions.just_pressed(GameAction::SwapHold) {
+        swap.send(SwapHold);
+    }
+}
+
+fn apply_preview_len(config: Res<LogicConfig>, mut queue: ResMut<PieceQueue>, mut rng: ResMut<GameRng>) {
+    if queue.upcoming.len() != config.preview_len {
+        queue.resize(config.preview_len, &mut *rng);
+    }
+}
+
+fn apply_hold(mut events: EventReader<SwapHold>, mut queue: ResMut<PieceQueue>, mut rng: ResMut<GameRng>) {
+    for _ in events.iter() {
+        queue.swap_hold(&mut *rng);
+    }
+}
+
+fn queue_window(
+    mut contexts: EguiContexts,
+    queue: Res<PieceQueue>,
+    mut swap: EventWriter<SwapHold>,
+    replay: Option<Res<ReplayPlayer>>,
+    state: Res<State<GameState>>,
+    tiers: Res<TierTable>
+) {
+    let name = |tier: usize| tiers.get(tier).map_or("?", |t| t.name.as_str());
+    egui::Window
+        ::new("Next")
+        .default_size([100.0, 150.0])
+        .default_pos([400.0, 0.0])
+        .default_open(true)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("Current: {}", name(queue.current)));
+            for (i, tier) in queue.upcoming.iter().enumerate() {
+                ui.label(format!("{}. {}", i + 1, name(*tier)));
+            }
+            ui.separator();
+            match queue.hold {
+                None => ui.label("Hold: empty"),
+                Some(tier) => ui.label(format!("Hold: {}", name(tier))),
+            };
+            let playing = *state.get() == GameState::Playing;
+            ui.add_enabled_ui(playing && !queue.hold_used && replay.is_none(), |ui| {
+                if ui.button("Swap hold (Q)").clicked() {
+                    swap.send(SwapHold);
+                }
+            });
+        });
+}
@@ -0,0 +1,49 @@
+use rand::{ RngCore, SeedableRng };
+use rand_chacha::ChaCha8Rng;
+use serde::{ Deserialize, Serialize };
+
+use crate::prelude::*;
+
+/// Every random choice the game makes is drawn from here, so a seed reproduces a run.
+/// ChaCha8 gives the same stream on every platform and serializes with its position.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct GameRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl FromWorld for GameRng {
+    fn from_world(world: &mut World) -> Self {
+        let seed = world.resource::<LogicConfig>().seed.unwrap_or_else(rand::random);
+        info!("Seeding the game with {}", seed);
+        Self::new(seed)
+    }
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, rng: ChaCha8Rng::seed_from_u64(seed) }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
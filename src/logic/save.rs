@@ -0,0 +1,181 @@
+use bevy::ecs::system::SystemParam;
+use serde::{ Deserialize, Serialize };
+
+use crate::{ prelude::*, game::{ danger::Danger, snow::Snow, GameState }, replay::Recorder };
+
+use super::{
+    assets::GeneratedAssets,
+    body::Body,
+    merge::{ despawn_claimed, Claimed },
+    queue::PieceQueue,
+    rng::GameRng,
+    score::Score,
+    tiers::TierTable,
+    undo::History,
+};
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveGame>()
+            .add_event::<LoadGame>()
+            .add_systems(Update, save_game)
+            // after this frame's merges have spawned, so none land on the loaded board
+            .add_systems(PostUpdate, load_game.before(despawn_claimed));
+    }
+}
+
+/// Writes the board to a RON file at this path
+#[derive(Event, Clone, Debug)]
+pub struct SaveGame(pub String);
+
+/// Replaces the board with one saved at this path
+#[derive(Event, Clone, Debug)]
+pub struct LoadGame(pub String);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedBody {
+    pub tier: usize,
+    pub pos: Vec2,
+    /// Where the body's up points, as passed to Snow::spawn
+    pub dir: Vec2,
+    pub linvel: Vec2,
+    pub angvel: f32,
+}
+
+/// Everything needed to carry on a run where it was left
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SaveFile {
+    pub bodies: Vec<SavedBody>,
+    pub score: Score,
+    pub queue: PieceQueue,
+    pub rng: GameRng,
+}
+
+impl SaveFile {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs
+            ::read_to_string(path)
+            .map_err(|e| format!("Couldn't read save {}: {}", path, e))?;
+        ron::from_str(&text).map_err(|e| format!("Couldn't parse save {}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = ron::ser
+            ::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| format!("Couldn't write save {}: {}", path, e))
+    }
+}
+
+/// The parts of the world a SaveFile covers
+#[derive(SystemParam)]
+pub struct Board<'w, 's> {
+    commands: Commands<'w, 's>,
+    snow: Query<'w, 's, (Entity, &'static Body, &'static Transform, &'static Velocity), (With<Snow>, Without<Claimed>)>,
+    assets: Res<'w, GeneratedAssets>,
+    tiers: Res<'w, TierTable>,
+    config: Res<'w, GameConfig>,
+    score: ResMut<'w, Score>,
+    queue: ResMut<'w, PieceQueue>,
+    rng: ResMut<'w, GameRng>,
+    danger: ResMut<'w, Danger>,
+    next_state: ResMut<'w, NextState<GameState>>,
+}
+
+impl<'w, 's> Board<'w, 's> {
+    pub fn capture(&self) -> SaveFile {
+        let bodies = self.snow
+            .iter()
+            .map(|(_, body, tf, vel)| SavedBody {
+                tier: body.tier,
+                pos: tf.translation.truncate(),
+                dir: (tf.rotation * Vec3::Y).truncate(),
+                linvel: vel.linvel,
+                angvel: vel.angvel,
+            })
+            .collect();
+        SaveFile {
+            bodies,
+            score: self.score.clone(),
+            queue: self.queue.clone(),
+            rng: self.rng.clone(),
+        }
+    }
+
+    /// Swaps the current bodies for the saved ones, respawned through Snow::spawn, and puts the run back in play
+    pub fn restore(&mut self, file: SaveFile) -> Result<(), String> {
+        if let Some(bad) = file.bodies.iter().find(|b| self.tiers.get(b.tier).is_none()) {
+            return Err(format!("Tier {} isn't in the tier table", bad.tier));
+        }
+        // the dropper and hold slot index the table with these on the next frame
+        if let Some(bad) = file.queue.missing_tier(&self.tiers) {
+            return Err(format!("Queued tier {} isn't in the tier table", bad));
+        }
+
+        for (entity, ..) in self.snow.iter() {
+            self.commands.entity(entity).despawn_recursive();
+        }
+        for saved in file.bodies.iter() {
+            let body = Snow::spawn(
+                saved.pos,
+                saved.dir,
+                saved.tier,
+                &mut self.commands,
+                &self.assets,
+                &self.tiers,
+                &self.config
+            );
+            self.commands.entity(body).insert(Velocity { linvel: saved.linvel, angvel: saved.angvel });
+        }
+        *self.score = file.score;
+        *self.queue = file.queue;
+        *self.rng = file.rng;
+        self.danger.over.clear();
+        self.next_state.set(GameState::Playing);
+        Ok(())
+    }
+}
+
+fn save_game(mut events: EventReader<SaveGame>, board: Board) {
+    for SaveGame(path) in events.iter() {
+        let file = board.capture();
+        match file.save(path) {
+            Ok(()) => info!("Saved {} bodies to {}", file.bodies.len(), path),
+            Err(e) => warn!("{}", e),
+        }
+    }
+}
+
+fn load_game(
+    mut events: EventReader<LoadGame>,
+    mut board: Board,
+    mut history: ResMut<History>,
+    recorder: Option<Res<Recorder>>
+) {
+    // only the last load of a frame matters
+    let Some(LoadGame(path)) = events.iter().last() else {
+        return;
+    };
+    // a replay can't follow a load, so it's off while recording
+    if recorder.is_some() {
+        warn!("Can't load {} while recording, the replay couldn't follow it", path);
+        return;
+    }
+    let result = SaveFile::load(path).and_then(|file| {
+        let count = file.bodies.len();
+        board
+            .restore(file)
+            .map(|()| count)
+            .map_err(|e| format!("Couldn't load {}: {}", path, e))
+    });
+    match result {
+        Ok(count) => {
+            // undo shouldn't bring back the board that was replaced
+            history.clear();
+            info!("Loaded {} bodies from {}", count, path);
+        }
+        Err(e) => warn!("{}", e),
+    }
+}
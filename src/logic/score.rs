@@ -0,0 +1,113 @@
+use bevy_egui::{ egui, EguiContexts };
+
+use serde::{ Deserialize, Serialize };
+
+use crate::{ prelude::*, replay::SimTime };
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Score>();
+        if has_egui(app) {
+            app.add_systems(Update, score_window);
+        }
+    }
+}
+
+#[derive(Resource, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Score {
+    pub points: u64,
+    pub merges: u32,
+    /// Merges in the current chain, 0 when no chain is running
+    pub chain: u32,
+    pub best_chain: u32,
+    /// SimTime of the last merge, not saved since a loaded game runs on a different clock
+    #[serde(skip)]
+    last_merge: Option<f32>,
+}
+
+impl Score {
+    /// Records a merge worth `base` points at `now` simulated seconds, returning the points awarded.
+    /// Merges within the chain window of the previous one extend the chain and earn a bonus.
+    pub fn record_merge(&mut self, base: u64, now: f32, config: &LogicConfig) -> u64 {
+        match self.last_merge {
+            Some(last) if now - last <= config.chain_window => {
+                self.chain += 1;
+            }
+            _ => {
+                self.chain = 1;
+            }
+        }
+        self.last_merge = Some(now);
+        self.best_chain = self.best_chain.max(self.chain);
+        self.merges += 1;
+
+        let multiplier = 1.0 + ((self.chain - 1) as f32) * config.chain_bonus;
+        let points = ((base as f32) * multiplier).round() as u64;
+        self.points += points;
+        points
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Whether a chain is still open at `now`
+    pub fn chain_active(&self, now: f32, config: &LogicConfig) -> bool {
+        self.last_merge.is_some_and(|last| now - last <= config.chain_window)
+    }
+}
+
+fn score_window(
+    mut contexts: EguiContexts,
+    score: Res<Score>,
+    sim_time: Res<SimTime>,
+    config: Res<LogicConfig>
+) {
+    let now = sim_time.0;
+    egui::Window
+        ::new("Score")
+        .default_size([100.0, 100.0])
+        .default_pos([250.0, 0.0])
+        .default_open(true)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.heading(format!("{}", score.points));
+            ui.label(format!("Merges: {}", score.merges));
+            if score.chain_active(now, &config) && score.chain > 1 {
+                ui.label(format!("Chain x{}", score.chain));
+            }
+            ui.label(format!("Best chain: {}", score.best_chain));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_in_the_window_chain() {
+        let config = LogicConfig { chain_window: 1.0, chain_bonus: 0.5, ..default() };
+        let mut score = Score::default();
+        assert_eq!(score.record_merge(10, 0.0, &config), 10);
+        assert_eq!(score.record_merge(10, 0.5, &config), 15);
+        assert_eq!(score.record_merge(10, 1.5, &config), 20);
+        assert_eq!(score.chain, 3);
+        assert!(score.chain_active(2.5, &config));
+        assert!(!score.chain_active(2.6, &config));
+
+        // too late, a new chain starts
+        assert_eq!(score.record_merge(10, 3.0, &config), 10);
+        assert_eq!(score.chain, 1);
+        assert_eq!(score.best_chain, 3);
+        assert_eq!(score.merges, 4);
+        assert_eq!(score.points, 55);
+    }
+
+    #[test]
+    fn no_chain_before_the_first_merge() {
+        let config = LogicConfig::default();
+        let score = Score::default();
+        assert!(!score.chain_active(0.0, &config));
+    }
+}
@@ -3,16 +3,12 @@ use rand::Rng;
 use crate::prelude::*;
 
 /// Return whether this location is open for spawning
-pub fn spawn(radius: f32, pos: Vec2, replacing: Option<Entity>, rc: &Res<RapierContext>) -> bool {
+pub fn spawn(radius: f32, pos: Vec2, filter: QueryFilter, rc: &Res<RapierContext>) -> bool {
     let mut space_open = true;
 
     let rot = 0.0;
-    let mut filter = QueryFilter::default();
-    if let Some(r) = replacing {
-        filter.exclude_collider(r);
-    }
-    rc.intersections_with_shape(pos, rot, &Collider::ball(radius), filter, |ix| {
-        // if there is an intersection, and it's not one of the bodies we're replacing
+    rc.intersections_with_shape(pos, rot, &Collider::ball(radius), filter, |_| {
+        // any intersection the filter lets through blocks the spawn
         space_open = false;
         false
     });
@@ -20,6 +16,29 @@ pub fn spawn(radius: f32, pos: Vec2, replacing: Option<Entity>, rc: &Res<RapierC
     space_open
 }
 
+/// Walks `pos` towards the middle of the container in `step` sized moves until a body of
+/// `radius` no longer overlaps the walls or floor, giving up after `steps`
+pub fn clear_of_walls(
+    radius: f32,
+    pos: Vec2,
+    step: f32,
+    steps: usize,
+    rc: &Res<RapierContext>
+) -> Vec2 {
+    let mut pos = pos;
+    for _ in 0..steps {
+        if spawn(radius, pos, QueryFilter::only_fixed(), rc) {
+            break;
+        }
+        let to_center = -pos;
+        if to_center.length() < step {
+            break;
+        }
+        pos += to_center.normalize() * step;
+    }
+    pos
+}
+
 // let wobble =
 // (Vec3::new(
 //     rand::thread_rng().gen_range(-1.0..1.0),
@@ -0,0 +1,79 @@
+use serde::{ Deserialize, Serialize };
+
+use crate::prelude::*;
+
+use super::assets::NAMED_COLORS;
+
+/// The shipped ladder, used when LogicConfig::tiers_path can't be loaded
+const BUILT_IN_TIERS: &str = include_str!("../../assets/tiers.ron");
+
+/// One step of the merge ladder, two bodies of a tier merge into the next one
+#[derive(Serialize, Deserialize, Clone, Debug, Reflect)]
+pub struct Tier {
+    pub name: String,
+    /// In the same units as Body::radius, multiplied by GameConfig::radius for pixels
+    pub radius: f32,
+    /// One of assets::NAMED_COLORS
+    pub color: String,
+    /// Base points for producing a body of this tier
+    pub score: u64,
+    pub density: f32,
+    pub restitution: f32,
+    pub friction: f32,
+}
+
+/// The tier ladder, loaded from LogicConfig::tiers_path so it can be rebalanced without recompiling
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+pub struct TierTable {
+    /// Only the first `drop_tiers` tiers are handed out as pieces
+    pub drop_tiers: usize,
+    /// Points for two max-tier bodies bursting
+    pub max_merge_bonus: u64,
+    pub tiers: Vec<Tier>,
+}
+
+impl Default for TierTable {
+    fn default() -> Self {
+        ron::from_str(BUILT_IN_TIERS).expect("Built-in tier table parses")
+    }
+}
+
+impl TierTable {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs
+            ::read_to_string(path)
+            .map_err(|e| format!("Couldn't read tier table {}: {}", path, e))?;
+        let table: TierTable = ron
+            ::from_str(&text)
+            .map_err(|e| format!("Couldn't parse tier table {}: {}", path, e))?;
+        if table.tiers.is_empty() {
+            return Err(format!("Tier table {} has no tiers", path));
+        }
+        // an unknown color would leave the tier without a material, and its bodies invisible
+        if let Some(tier) = table.tiers.iter().find(|t| !NAMED_COLORS.iter().any(|(name, _)| *name == t.color)) {
+            return Err(format!("Tier {} in {} has unknown color {}", tier.name, path, tier.color));
+        }
+        Ok(table)
+    }
+
+    pub fn get(&self, tier: usize) -> Option<&Tier> {
+        self.tiers.get(tier)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiers.is_empty()
+    }
+
+    pub fn max_tier(&self) -> usize {
+        self.tiers.len() - 1
+    }
+
+    /// Number of tiers pieces are drawn from, never more than exist
+    pub fn drop_range(&self) -> usize {
+        self.drop_tiers.clamp(1, self.tiers.len())
+    }
+}
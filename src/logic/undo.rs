@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+
+use crate::prelude::*;
+
+use super::{
+    drop::release_drop,
+    hud::SelectedPos,
+    merge::despawn_claimed,
+    save::{ Board, SaveFile },
+    spawn_on_click,
+};
+
+pub struct UndoPlugin;
+
+impl Plugin for UndoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<History>()
+            .add_event::<UndoDrop>()
+            .add_systems(Update, snapshot_before_drop.after(release_drop).before(spawn_on_click))
+            .add_systems(PostUpdate, undo_drop.before(despawn_claimed));
+    }
+}
+
+/// Puts the board back the way it was before the last drop
+#[derive(Event, Clone, Copy, Debug, Default)]
+pub struct UndoDrop;
+
+/// The board as it was before each of the last LogicConfig::undo_depth drops, newest last
+#[derive(Resource, Default)]
+pub struct History {
+    pub snapshots: VecDeque<SaveFile>,
+}
+
+impl History {
+    /// Adds the newest snapshot, dropping the oldest ones past `depth`
+    pub fn push(&mut self, snapshot: SaveFile, depth: usize) {
+        if depth == 0 {
+            return;
+        }
+        while self.snapshots.len() >= depth {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Forgets every snapshot, so undo can't reach into another game
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
+
+fn snapshot_before_drop(
+    sp: Res<SelectedPos>,
+    mut history: ResMut<History>,
+    board: Board,
+    config: Res<LogicConfig>
+) {
+    if sp.0.is_none() || config.undo_depth == 0 {
+        return;
+    }
+    history.push(board.capture(), config.undo_depth);
+}
+
+fn undo_drop(mut events: EventReader<UndoDrop>, mut history: ResMut<History>, mut board: Board) {
+    // several undos in one frame step back that many drops
+    let Some(snapshot) = events
+        .iter()
+        .filter_map(|_| history.snapshots.pop_back())
+        .last() else {
+        return;
+    };
+    let count = snapshot.bodies.len();
+    match board.restore(snapshot) {
+        Ok(()) => info!("Undid a drop, back to {} bodies", count),
+        Err(e) => warn!("Couldn't undo: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::{ queue::PieceQueue, rng::GameRng, score::Score };
+
+    use super::*;
+
+    /// A snapshot told apart by its seed
+    fn snapshot(seed: u64) -> SaveFile {
+        let mut rng = GameRng::new(seed);
+        SaveFile {
+            bodies: Vec::new(),
+            score: Score::default(),
+            queue: PieceQueue::new(1, 3, &mut rng),
+            rng,
+        }
+    }
+
+    fn seeds(history: &History) -> Vec<u64> {
+        history.snapshots
+            .iter()
+            .map(|s| s.rng.seed())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_the_newest_snapshots() {
+        let mut history = History::default();
+        for seed in 0..5 {
+            history.push(snapshot(seed), 3);
+        }
+        assert_eq!(seeds(&history), vec![2, 3, 4]);
+
+        // a smaller depth from a reloaded config trims the rest
+        history.push(snapshot(5), 2);
+        assert_eq!(seeds(&history), vec![4, 5]);
+
+        history.push(snapshot(6), 0);
+        assert_eq!(seeds(&history), vec![4, 5]);
+    }
+
+    #[test]
+    fn clear_forgets_everything() {
+        let mut history = History::default();
+        history.push(snapshot(1), 3);
+        history.push(snapshot(2), 3);
+        history.clear();
+        assert!(history.snapshots.is_empty());
+    }
+}
@@ -1,23 +1,75 @@
-pub mod setup;
-pub mod prelude;
-pub mod controls;
-pub mod config;
-pub mod logic;
-pub mod game;
-
+use bevy::{ app::AppExit, core::FrameCount };
 use rand::Rng;
 
-use crate::prelude::*;
+use yuki::{
+    prelude::*,
+    logic::{ score::Score, rng::GameRng },
+    replay::{ save_recording, Replay, ReplayMode, ReplayOutcome },
+    ConfigSource,
+    YukiPlugins,
+};
+
+/// The value following `name` on the command line
+fn arg<'a>(args: &'a [String], name: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1))
+}
 
 fn main() {
-    App::new()
-        .add_plugins((
-            game::GamePlugin,
-            logic::LogicPlugin,
-            setup::SetupPlugin,
-            controls::ControlsPlugin,
-        ))
-        .run();
+    let args = std::env::args().collect::<Vec<_>>();
+
+    let mut plugins = if let Some(path) = arg(&args, "--replay") {
+        // a replay brings its own settings and seed, and isn't hot reloaded
+        let replay = Replay::load(path).expect("Replay loads");
+        let speed = arg(&args, "--speed").map_or(1.0, |s| s.parse::<f32>().expect("--speed takes a number"));
+        let mut config = replay.config.clone();
+        config.logic.seed = Some(replay.seed);
+        config.setup.fixed_timestep = true;
+        YukiPlugins { replay: ReplayMode::Play { replay, speed }, ..YukiPlugins::with_config(config) }
+    } else if let Some(path) = arg(&args, "--record") {
+        // recordings need fixed steps to play back, and no config changes part way through
+        let mut source = ConfigSource::from_args(&args);
+        source.overrides.push("setup.fixed_timestep=true".to_string());
+        let config = source.load().expect("Config loads");
+        YukiPlugins { replay: ReplayMode::Record(path.clone()), ..YukiPlugins::with_config(config) }
+    } else {
+        YukiPlugins::from_config(ConfigSource::from_args(&args)).expect("Config loads")
+    };
+
+    if args.iter().any(|a| a == "--headless") {
+        let frames = arg(&args, "--frames").map(|n| n.parse::<u32>().expect("--frames takes a number"));
+
+        plugins.headless = true;
+        let mut app = App::new();
+        app.add_plugins(plugins);
+        if let Some(frames) = frames {
+            app.add_systems(
+                Last,
+                (move |
+                    frame: Res<FrameCount>,
+                    mut exit: EventWriter<AppExit>,
+                    score: Res<Score>,
+                    rng: Res<GameRng>
+                | {
+                    if frame.0 >= frames {
+                        info!("Stopping after {} frames with {} points, seed {}", frame.0, score.points, rng.seed());
+                        exit.send(AppExit);
+                    }
+                }).before(save_recording)
+            );
+        }
+        // lets scripts use a replay as a regression check, the world is gone once run returns
+        app.add_systems(Last, |outcome: Option<Res<ReplayOutcome>>| {
+            if outcome.is_some_and(|o| !o.matched) {
+                std::process::exit(1);
+            }
+        });
+        app.run();
+        return;
+    }
+
+    App::new().add_plugins(plugins).run();
 }
 
 #[test]
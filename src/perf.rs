@@ -0,0 +1,273 @@
+use std::{ collections::VecDeque, fs::File, io::Write, time::Instant };
+
+use bevy::{ core::FrameCount, time::Stopwatch };
+use bevy_egui::{ egui, EguiContexts };
+use egui::plot::{ Bar, BarChart, Legend, Line, Plot, PlotPoints };
+
+use crate::{
+    prelude::*,
+    game::snow::Snow,
+    logic::{ body::Body, score::Score, tiers::TierTable },
+    replay::SimFrame,
+};
+
+pub const PERF_CSV_PATH: &str = "perf.csv";
+/// Seconds of history kept for the plots
+pub const PERF_HISTORY_SECS: usize = 600;
+
+/// The Stats window: frame rate, physics cost and what the simulation is doing, sampled once a second
+pub struct PerfPlugin;
+
+impl Plugin for PerfPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Perf>()
+            .add_systems(
+                PostUpdate,
+                (
+                    start_step_timer.after(PhysicsSet::SyncBackend).before(PhysicsSet::StepSimulation),
+                    stop_step_timer.after(PhysicsSet::StepSimulation).before(PhysicsSet::Writeback),
+                )
+            )
+            .add_systems(Last, sample_perf);
+        if has_egui(app) {
+            app.add_systems(Last, stats_window.after(sample_perf));
+        }
+    }
+}
+
+/// One second of measurements
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PerfSample {
+    /// Seconds since startup
+    pub second: f64,
+    pub fps: f64,
+    /// Average frame time in milliseconds
+    pub frame_ms: f64,
+    /// Average rapier step time in milliseconds
+    pub step_ms: f64,
+    pub collisions: u32,
+    pub merges: u32,
+    pub bodies: usize,
+}
+
+impl PerfSample {
+    pub const CSV_HEADER: &str = "second,fps,frame_ms,step_ms,collisions,merges,bodies";
+
+    pub fn csv_row(&self) -> String {
+        format!(
+            "{:.3},{:.2},{:.3},{:.3},{},{},{}",
+            self.second,
+            self.fps,
+            self.frame_ms,
+            self.step_ms,
+            self.collisions,
+            self.merges,
+            self.bodies
+        )
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Perf {
+    pub samples: VecDeque<PerfSample>,
+    /// Open while samples are being written to PERF_CSV_PATH
+    export: Option<File>,
+    timer: Stopwatch,
+    frames: u32,
+    frame_secs: f64,
+    steps: u32,
+    step_secs: f64,
+    step_started: Option<Instant>,
+    collisions: u32,
+    last_merges: u32,
+}
+
+impl Perf {
+    pub fn exporting(&self) -> bool {
+        self.export.is_some()
+    }
+
+    /// Starts writing to PERF_CSV_PATH with the history so far, or stops
+    pub fn set_exporting(&mut self, on: bool) {
+        if on == self.exporting() {
+            return;
+        }
+        if !on {
+            self.export = None;
+            info!("Stopped exporting to {}", PERF_CSV_PATH);
+            return;
+        }
+        let file = File::create(PERF_CSV_PATH).and_then(|mut file| {
+            writeln!(file, "{}", PerfSample::CSV_HEADER)?;
+            for sample in self.samples.iter() {
+                writeln!(file, "{}", sample.csv_row())?;
+            }
+            Ok(file)
+        });
+        match file {
+            Ok(file) => {
+                self.export = Some(file);
+                info!("Exporting to {}", PERF_CSV_PATH);
+            }
+            Err(e) => warn!("Couldn't export to {}: {}", PERF_CSV_PATH, e),
+        }
+    }
+
+    fn push(&mut self, sample: PerfSample) {
+        if let Some(file) = self.export.as_mut() {
+            if let Err(e) = writeln!(file, "{}", sample.csv_row()) {
+                warn!("Stopped exporting to {}: {}", PERF_CSV_PATH, e);
+                self.export = None;
+            }
+        }
+        if self.samples.len() >= PERF_HISTORY_SECS {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    fn line(&self, value: impl Fn(&PerfSample) -> f64) -> PlotPoints {
+        self.samples
+            .iter()
+            .map(|s| [s.second, value(s)])
+            .collect()
+    }
+}
+
+fn start_step_timer(mut perf: ResMut<Perf>, rapier_config: Res<RapierConfiguration>) {
+    if rapier_config.physics_pipeline_active {
+        perf.step_started = Some(Instant::now());
+    }
+}
+
+fn stop_step_timer(mut perf: ResMut<Perf>) {
+    if let Some(started) = perf.step_started.take() {
+        perf.step_secs += started.elapsed().as_secs_f64();
+        perf.steps += 1;
+    }
+}
+
+fn sample_perf(
+    mut perf: ResMut<Perf>,
+    mut collisions: EventReader<CollisionEvent>,
+    score: Res<Score>,
+    bodies: Query<(), With<Snow>>,
+    time: Res<Time>
+) {
+    perf.timer.tick(time.delta());
+    perf.frames += 1;
+    perf.frame_secs += time.delta_seconds_f64();
+    perf.collisions += collisions
+        .iter()
+        .filter(|e| matches!(e, CollisionEvent::Started(..)))
+        .count() as u32;
+
+    if perf.timer.elapsed_secs() < 1.0 {
+        return;
+    }
+
+    let frame_secs = perf.frame_secs / (perf.frames.max(1) as f64);
+    let sample = PerfSample {
+        second: time.elapsed_seconds_f64(),
+        fps: if frame_secs > 0.0 { 1.0 / frame_secs } else { 0.0 },
+        frame_ms: frame_secs * 1000.0,
+        step_ms: (perf.step_secs / (perf.steps.max(1) as f64)) * 1000.0,
+        collisions: perf.collisions,
+        // restarts put the count back to zero
+        merges: score.merges.saturating_sub(perf.last_merges),
+        bodies: bodies.iter().count(),
+    };
+    perf.push(sample);
+
+    perf.timer.reset();
+    perf.frames = 0;
+    perf.frame_secs = 0.0;
+    perf.steps = 0;
+    perf.step_secs = 0.0;
+    perf.collisions = 0;
+    perf.last_merges = score.merges;
+}
+
+fn stats_window(
+    mut contexts: EguiContexts,
+    mut perf: ResMut<Perf>,
+    bodies: Query<&Body, With<Snow>>,
+    tiers: Res<TierTable>,
+    frame: Res<FrameCount>,
+    sim_frame: Res<SimFrame>
+) {
+    let mut per_tier = vec![0; tiers.len()];
+    for body in bodies.iter() {
+        if let Some(count) = per_tier.get_mut(body.tier) {
+            *count += 1;
+        }
+    }
+
+    egui::Window
+        ::new("Stats")
+        .default_size([300.0, 500.0])
+        .default_pos([0.0, -1000.0])
+        .default_open(true)
+        .show(contexts.ctx_mut(), |ui| {
+            let latest = perf.samples.back().copied().unwrap_or_default();
+            ui.label(
+                format!(
+                    "Second {:.0}:\tFPS {:.1}, frame {:.2}ms, physics step {:.2}ms",
+                    latest.second,
+                    latest.fps,
+                    latest.frame_ms,
+                    latest.step_ms
+                )
+            );
+            ui.label(format!("Frame {}, physics step {}", frame.0, sim_frame.0));
+            ui.label(
+                format!(
+                    "{} bodies, {} collisions/s, {} merges/s",
+                    latest.bodies,
+                    latest.collisions,
+                    latest.merges
+                )
+            );
+
+            let mut exporting = perf.exporting();
+            if ui.checkbox(&mut exporting, format!("Export to {}", PERF_CSV_PATH)).changed() {
+                perf.set_exporting(exporting);
+            }
+
+            ui.label("FPS");
+            Plot::new("fps")
+                .height(100.0)
+                .show(ui, |plot| plot.line(Line::new(perf.line(|s| s.fps))));
+
+            ui.label("Milliseconds");
+            Plot::new("times")
+                .height(100.0)
+                .legend(Legend::default())
+                .show(ui, |plot| {
+                    plot.line(Line::new(perf.line(|s| s.frame_ms)).name("frame"));
+                    plot.line(Line::new(perf.line(|s| s.step_ms)).name("physics step"));
+                });
+
+            ui.label("Per second");
+            Plot::new("events")
+                .height(100.0)
+                .legend(Legend::default())
+                .show(ui, |plot| {
+                    plot.line(Line::new(perf.line(|s| s.collisions as f64)).name("collisions"));
+                    plot.line(Line::new(perf.line(|s| s.merges as f64)).name("merges"));
+                });
+
+            ui.label("Bodies per tier");
+            let bars = per_tier
+                .iter()
+                .enumerate()
+                .map(|(tier, count)| {
+                    let name = tiers.get(tier).map_or("?", |t| t.name.as_str());
+                    Bar::new(tier as f64, *count as f64).name(name)
+                })
+                .collect();
+            Plot::new("tiers")
+                .height(100.0)
+                .show(ui, |plot| plot.bar_chart(BarChart::new(bars)));
+        });
+}
@@ -23,11 +23,25 @@ pub fn make_render(
     }
 }
 
+/// Marks an app running without a window or renderer, plugins leave out their ui and drawing when it's present
+#[derive(Resource, Default, Clone, Copy)]
+pub struct Headless;
+
+pub fn is_headless(app: &App) -> bool {
+    app.world.contains_resource::<Headless>()
+}
+
+/// Whether egui windows can be added: the app has a window and EguiPlugin was added before this plugin
+pub fn has_egui(app: &App) -> bool {
+    !is_headless(app) && app.is_plugin_added::<bevy_egui::EguiPlugin>()
+}
+
+/// Everything a body needs to simulate. Rendering is added separately so the same body works headless.
 #[derive(Bundle, Clone)]
 pub struct PhysicsBody {
     pub body: RigidBody,
     pub collider: Collider,
-    pub render: Render,
+    pub transform: TransformBundle,
     pub x_f: ExternalForce,
     pub x_i: ExternalImpulse,
     pub vel: Velocity,
@@ -35,17 +49,17 @@ pub struct PhysicsBody {
 }
 
 impl PhysicsBody {
-    pub fn new(collider: Collider, render: Render) -> Self {
+    pub fn new(collider: Collider, transform: Transform, config: &GameConfig) -> Self {
         Self {
             collider,
-            render,
+            transform: TransformBundle::from_transform(transform),
             body: RigidBody::Dynamic,
             x_f: ExternalForce::default(),
             x_i: ExternalImpulse::default(),
             vel: Velocity::default(),
             damping: Damping {
-                linear_damping: DEFAULT_LIN_DAMPING,
-                angular_damping: DEFAULT_ANG_DAMPING,
+                linear_damping: config.lin_damping,
+                angular_damping: config.ang_damping,
             },
         }
     }
@@ -56,8 +70,9 @@ impl PhysicsBody {
         pos: Vec2,
         dir: Vec2,
         meshes: &mut ResMut<Assets<Mesh>>,
-        materials: &mut ResMut<Assets<ColorMaterial>>
-    ) -> Self {
+        materials: &mut ResMut<Assets<ColorMaterial>>,
+        config: &GameConfig
+    ) -> (Self, Render) {
         let collider = Collider::ball(radius);
         let mesh = Mesh::from(shape::Circle::new(radius));
         let mut transform = Transform::from_xyz(pos.x, pos.y, VISIBLE_Z);
@@ -68,16 +83,11 @@ impl PhysicsBody {
             transform,
             ..Default::default()
         };
-        Self::new(collider, render)
-    }
-
-    pub fn color(mut self, color: Handle<ColorMaterial>) -> Self {
-        self.render.material = color;
-        self
+        (Self::new(collider, transform, config), render)
     }
 
     pub fn pos(mut self, pos: Vec3) -> Self {
-        self.render.transform = Transform::from_translation(pos);
+        self.transform = TransformBundle::from_transform(Transform::from_translation(pos));
         self
     }
 
@@ -85,13 +95,10 @@ impl PhysicsBody {
         mesh: Mesh2dHandle,
         material: Handle<ColorMaterial>,
         collider: Collider,
-        pos: Vec3
-    ) -> Self {
-        Self::new(collider, Render {
-            mesh: mesh.into(),
-            material,
-            transform: Transform::from_translation(pos),
-            ..Default::default()
-        })
+        pos: Vec3,
+        config: &GameConfig
+    ) -> (Self, Render) {
+        let transform = Transform::from_translation(pos);
+        (Self::new(collider, transform, config), make_render(mesh, material, transform))
     }
 }
@@ -0,0 +1,436 @@
+use bevy::{ app::AppExit, transform::TransformSystem };
+use bevy_egui::{ egui, EguiContexts };
+use bevy_window::PrimaryWindow;
+use serde::{ Deserialize, Serialize };
+
+use crate::{
+    prelude::*,
+    config::YukiConfig,
+    game::{ danger::RestartGame, GameState },
+    logic::{ body::Body, drop::release_drop, hud::{ CameraFollows, SelectedPos }, queue::SwapHold, rng::GameRng, undo::UndoDrop, spawn_on_click },
+    setup::MoveCamera,
+};
+
+/// Seconds between saves of a recording in progress
+pub const RECORD_SAVE_SECS: f32 = 10.0;
+/// Replays take at most one fixed step a frame, so they only play slower than recorded
+pub const MIN_REPLAY_SPEED: f32 = 0.1;
+pub const MAX_REPLAY_SPEED: f32 = 1.0;
+
+/// Records player actions to a RON replay file, or plays one back through the same systems
+#[derive(Default)]
+pub struct ReplayPlugin {
+    pub mode: ReplayMode,
+}
+
+#[derive(Default, Clone)]
+pub enum ReplayMode {
+    #[default]
+    Off,
+    /// Record to this path, saved every RECORD_SAVE_SECS and on exit
+    Record(String),
+    /// Play back a replay, `speed` times as fast as it was recorded, up to MAX_REPLAY_SPEED
+    Play {
+        replay: Replay,
+        speed: f32,
+    },
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        // camera actions still play back when headless, with nothing to move
+        app.init_resource::<SimFrame>().init_resource::<SimTime>().add_event::<MoveCamera>().add_systems(
+            PostUpdate,
+            count_sim_frames.after(PhysicsSet::Writeback)
+        );
+
+        match &self.mode {
+            ReplayMode::Off => {}
+            ReplayMode::Record(path) => {
+                app.insert_resource(Recorder {
+                    path: path.clone(),
+                    replay: Replay::default(),
+                    since_save: 0.0,
+                })
+                    .add_systems(Startup, start_recording)
+                    .add_systems(Update, record_drops.after(release_drop).before(spawn_on_click))
+                    // after transforms propagate, so follow_camera's move is seen the frame it happens
+                    .add_systems(
+                        PostUpdate,
+                        record_actions.after(TransformSystem::TransformPropagate).before(count_sim_frames)
+                    )
+                    .add_systems(Last, save_recording);
+            }
+            ReplayMode::Play { replay, speed } => {
+                // replays go straight into the run rather than waiting at the main menu
+                app.insert_resource(State::new(GameState::Playing)).insert_resource(ReplayPlayer {
+                    replay: replay.clone(),
+                    next: 0,
+                    speed: speed.clamp(MIN_REPLAY_SPEED, MAX_REPLAY_SPEED),
+                    ticks: 0.0,
+                }).add_systems(PreUpdate, play_actions);
+                if has_egui(app) {
+                    app.add_systems(Update, replay_window.run_if(replaying));
+                }
+            }
+        }
+    }
+}
+
+/// Physics steps taken so far. Replay actions are lined up against this rather than FrameCount,
+/// so frames where the simulation is frozen or held back don't throw them off
+#[derive(Resource, Default, Deref, Debug, Clone, Copy)]
+pub struct SimFrame(pub u32);
+
+/// Seconds simulated so far, for timings that have to match between a run and its replay
+#[derive(Resource, Default, Deref, Debug, Clone, Copy)]
+pub struct SimTime(pub f32);
+
+/// Something the player did that changes the run
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Action {
+    /// A piece released at this position through SelectedPos
+    Drop(Vec2),
+    SwapHold,
+    Restart,
+    Undo,
+    Camera {
+        to: Vec2,
+        scale: f32,
+    },
+}
+
+/// A recorded session: everything needed to play it again, and how it ended
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Replay {
+    pub seed: u64,
+    pub config: YukiConfig,
+    /// Actions with the SimFrame they happened on, in order
+    pub actions: Vec<(u32, Action)>,
+    /// SimFrame when the recording stopped
+    pub end: u32,
+    /// Sorted tiers of the bodies left at the end, to check a playback against
+    pub tiers: Vec<usize>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs
+            ::read_to_string(path)
+            .map_err(|e| format!("Couldn't read replay {}: {}", path, e))?;
+        ron::from_str(&text).map_err(|e| format!("Couldn't parse replay {}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = ron::ser
+            ::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| format!("Couldn't write replay {}: {}", path, e))
+    }
+}
+
+#[derive(Resource)]
+pub struct Recorder {
+    pub path: String,
+    pub replay: Replay,
+    since_save: f32,
+}
+
+impl Recorder {
+    fn push(&mut self, frame: &SimFrame, action: Action) {
+        self.replay.actions.push((frame.0, action));
+    }
+}
+
+/// Present while a replay is playing, player input is ignored until it ends
+#[derive(Resource)]
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    /// Index of the next action to play
+    pub next: usize,
+    pub speed: f32,
+    /// Physics steps owed at the current speed
+    ticks: f32,
+}
+
+/// How a finished replay compared with its recording
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct ReplayOutcome {
+    pub matched: bool,
+}
+
+pub fn replaying(player: Option<Res<ReplayPlayer>>) -> bool {
+    player.is_some()
+}
+
+pub fn recording(recorder: Option<Res<Recorder>>) -> bool {
+    recorder.is_some()
+}
+
+fn count_sim_frames(
+    mut frame: ResMut<SimFrame>,
+    mut sim_time: ResMut<SimTime>,
+    rapier_config: Res<RapierConfiguration>,
+    time: Res<Time>
+) {
+    if rapier_config.physics_pipeline_active {
+        frame.0 += 1;
+        sim_time.0 += match rapier_config.timestep_mode {
+            TimestepMode::Fixed { dt, .. } => dt,
+            _ => time.delta_seconds(),
+        };
+    }
+}
+
+fn sorted_tiers(bodies: &Query<&Body>) -> Vec<usize> {
+    let mut tiers = bodies
+        .iter()
+        .map(|b| b.tier)
+        .collect::<Vec<_>>();
+    tiers.sort();
+    tiers
+}
+
+/// The replay starts from the settings and seed the game actually started with
+fn start_recording(
+    mut recorder: ResMut<Recorder>,
+    rng: Res<GameRng>,
+    game: Res<GameConfig>,
+    logic: Res<LogicConfig>,
+    setup: Res<SetupConfig>,
+    controls: Option<Res<ControlsConfig>>
+) {
+    recorder.replay.seed = rng.seed();
+    recorder.replay.config = YukiConfig {
+        game: game.clone(),
+        logic: logic.clone(),
+        setup: setup.clone(),
+        controls: controls.map(|c| c.clone()).unwrap_or_default(),
+    };
+    info!("Recording to {}", recorder.path);
+}
+
+fn record_drops(mut recorder: ResMut<Recorder>, sp: Res<SelectedPos>, frame: Res<SimFrame>) {
+    if let Some(pos) = sp.0 {
+        recorder.push(&frame, Action::Drop(pos));
+    }
+}
+
+/// Camera moves are only recorded when the player made them. Following a body, a locked camera
+/// and fitting to a resized window move it too, those are taken as the new baseline instead.
+#[allow(clippy::too_many_arguments)]
+fn record_actions(
+    mut recorder: ResMut<Recorder>,
+    frame: Res<SimFrame>,
+    mut holds: EventReader<SwapHold>,
+    mut restarts: EventReader<RestartGame>,
+    mut undos: EventReader<UndoDrop>,
+    camera: Query<(&Transform, &OrthographicProjection), With<Camera>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    follows: Res<CameraFollows>,
+    controls: Option<Res<ControlsConfig>>,
+    mut last_camera: Local<Option<(Vec2, f32)>>,
+    mut last_window: Local<Option<Vec2>>
+) {
+    for _ in holds.iter() {
+        recorder.push(&frame, Action::SwapHold);
+    }
+    for _ in restarts.iter() {
+        recorder.push(&frame, Action::Restart);
+    }
+    for _ in undos.iter() {
+        recorder.push(&frame, Action::Undo);
+    }
+    let window = windows.get_single().ok().map(|w| Vec2::new(w.width(), w.height()));
+    let resized = *last_window != window;
+    *last_window = window;
+    let automatic = resized || follows.is_some() || controls.is_some_and(|c| c.lock_camera);
+    if let Ok((tf, proj)) = camera.get_single() {
+        let now = (tf.translation.truncate(), proj.scale);
+        if *last_camera != Some(now) {
+            *last_camera = Some(now);
+            if !automatic {
+                recorder.push(&frame, Action::Camera { to: now.0, scale: now.1 });
+            }
+        }
+    }
+}
+
+/// Saves now and then so a crash still leaves a replay behind, and once more on exit
+pub fn save_recording(
+    mut recorder: ResMut<Recorder>,
+    mut exit: EventReader<AppExit>,
+    frame: Res<SimFrame>,
+    bodies: Query<&Body>,
+    time: Res<Time>
+) {
+    recorder.since_save += time.delta_seconds();
+    let exiting = exit.iter().count() > 0;
+    if !exiting && recorder.since_save < RECORD_SAVE_SECS {
+        return;
+    }
+    recorder.since_save = 0.0;
+
+    recorder.replay.end = frame.0;
+    recorder.replay.tiers = sorted_tiers(&bodies);
+    match recorder.replay.save(&recorder.path) {
+        Ok(()) if exiting => info!("Saved replay to {}", recorder.path),
+        Ok(()) => {}
+        Err(e) => warn!("{}", e),
+    }
+}
+
+/// Feeds the actions due on this SimFrame back in, holding physics back when playing slower than recorded
+#[allow(clippy::too_many_arguments)]
+fn play_actions(
+    mut commands: Commands,
+    mut player: ResMut<ReplayPlayer>,
+    frame: Res<SimFrame>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    state: Res<State<GameState>>,
+    mut sp: ResMut<SelectedPos>,
+    mut holds: EventWriter<SwapHold>,
+    mut restarts: EventWriter<RestartGame>,
+    mut undos: EventWriter<UndoDrop>,
+    mut camera: EventWriter<MoveCamera>,
+    mut exit: EventWriter<AppExit>,
+    bodies: Query<&Body>,
+    headless: Option<Res<Headless>>,
+    time: Res<Time>,
+    setup: Res<SetupConfig>
+) {
+    if headless.is_none() && *state.get() == GameState::Playing {
+        player.ticks = (player.ticks + (time.delta_seconds() * player.speed) / setup.fixed_dt).min(1.0);
+        let tick = player.ticks >= 1.0;
+        rapier_config.physics_pipeline_active = tick;
+        if !tick {
+            return;
+        }
+        player.ticks -= 1.0;
+    }
+
+    let played_from = player.next;
+    while let Some((at, action)) = player.replay.actions.get(player.next).cloned() {
+        if at > frame.0 {
+            break;
+        }
+        match action {
+            Action::Drop(pos) => {
+                sp.0 = Some(pos);
+            }
+            Action::SwapHold => holds.send(SwapHold),
+            Action::Restart => restarts.send(RestartGame),
+            Action::Undo => undos.send(UndoDrop),
+            Action::Camera { to, scale } => camera.send(MoveCamera { to, scale: Some(scale) }),
+        }
+        player.next += 1;
+        // one drop per frame, the same as spawn_on_click saw them
+        if matches!(action, Action::Drop(_)) {
+            break;
+        }
+    }
+
+    let done = player.next >= player.replay.actions.len() && frame.0 >= player.replay.end;
+    // physics and so SimFrame stop at game over, the actions still due can never come
+    let stuck =
+        !done &&
+        *state.get() == GameState::GameOver &&
+        player.next == played_from &&
+        player.replay.actions.get(player.next).map_or(true, |(at, _)| *at > frame.0);
+    if !done && !stuck {
+        return;
+    }
+
+    let tiers = sorted_tiers(&bodies);
+    let matched = done && tiers == player.replay.tiers;
+    if matched {
+        info!("Replay finished on frame {} and matches its recording", frame.0);
+    } else if stuck {
+        warn!("Replay diverged, the game ended on frame {} of {}", frame.0, player.replay.end);
+    } else {
+        warn!(
+            "Replay finished on frame {} but diverged, expected tiers {:?} and got {:?}",
+            frame.0,
+            player.replay.tiers,
+            tiers
+        );
+    }
+    commands.insert_resource(ReplayOutcome { matched });
+    commands.remove_resource::<ReplayPlayer>();
+    rapier_config.physics_pipeline_active = *state.get() == GameState::Playing;
+    if headless.is_some() {
+        exit.send(AppExit);
+    }
+}
+
+fn replay_window(mut contexts: EguiContexts, mut player: ResMut<ReplayPlayer>, frame: Res<SimFrame>) {
+    egui::Window
+        ::new("Replay")
+        .default_pos([600.0, 0.0])
+        .default_open(true)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("Frame {} of {}", frame.0, player.replay.end));
+            ui.label(format!("Action {} of {}", player.next, player.replay.actions.len()));
+            ui.add(egui::Slider::new(&mut player.speed, MIN_REPLAY_SPEED..=MAX_REPLAY_SPEED).text("Speed"));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::YukiPlugins;
+
+    fn headless(plugins: YukiPlugins) -> App {
+        let mut app = App::new();
+        app.add_plugins(YukiPlugins { headless: true, ..plugins });
+        app.finish();
+        app.cleanup();
+        app
+    }
+
+    #[test]
+    fn recording_plays_back() {
+        let path = std::env::temp_dir()
+            .join(format!("yuki-replay-{}.ron", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+
+        // a few drops along the container with a hold in between
+        let mut app = headless(YukiPlugins {
+            logic: LogicConfig { seed: Some(4321), ..default() },
+            replay: ReplayMode::Record(path.clone()),
+            ..default()
+        });
+        for frame in 0..300 {
+            if frame % 60 == 0 {
+                let x = ((frame / 60) as f32 - 2.0) * 40.0;
+                app.world.resource_mut::<SelectedPos>().0 = Some(Vec2::new(x, 300.0));
+            }
+            if frame == 150 {
+                app.world.send_event(SwapHold);
+            }
+            app.update();
+        }
+        app.world.send_event(AppExit);
+        app.update();
+
+        let replay = Replay::load(&path).expect("Recording was saved");
+        std::fs::remove_file(&path).ok();
+        assert_eq!(replay.actions.iter().filter(|(_, a)| matches!(a, Action::Drop(_))).count(), 5);
+
+        // the same way main starts a replay
+        let mut config = replay.config.clone();
+        config.logic.seed = Some(replay.seed);
+        let mut app = headless(YukiPlugins {
+            replay: ReplayMode::Play { replay, speed: 1.0 },
+            ..YukiPlugins::with_config(config)
+        });
+        for _ in 0..1000 {
+            if app.world.contains_resource::<ReplayOutcome>() {
+                break;
+            }
+            app.update();
+        }
+        assert!(app.world.get_resource::<ReplayOutcome>().expect("Replay finished").matched);
+    }
+}
@@ -1,10 +1,7 @@
 use std::f32::consts::PI;
 
-use egui::plot::{ Line, Plot, PlotPoints };
-use crate::{ prelude::*, controls::GUISelect };
+use crate::{ prelude::*, controls::GUISelect, input::{ action_toggle_active, GameAction } };
 use bevy::{
-    input::common_conditions::input_toggle_active,
-    time::Stopwatch,
     render::{
         settings::{ WgpuFeatures, WgpuSettings },
         RenderPlugin,
@@ -13,7 +10,7 @@ use bevy::{
     },
     sprite::Mesh2dHandle,
 };
-use bevy_egui::{ egui, EguiContext, EguiPlugin, EguiContexts };
+use bevy_egui::{ egui, EguiContext, EguiPlugin };
 use bevy_inspector_egui::{
     self,
     bevy_inspector::hierarchy::SelectedEntities,
@@ -24,11 +21,15 @@ use bevy_prototype_debug_lines::*;
 use bevy_rapier2d::rapier::prelude::{ Isometry, ColliderBuilder };
 use bevy_window::PrimaryWindow;
 
-pub struct SetupPlugin;
+#[derive(Default)]
+pub struct SetupPlugin {
+    pub config: SetupConfig,
+}
 
 impl Plugin for SetupPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(
+        app.insert_resource(self.config.clone())
+            .add_plugins(
             DefaultPlugins.set(RenderPlugin {
                 wgpu_settings: WgpuSettings {
                     features: WgpuFeatures::POLYGON_MODE_LINE,
@@ -37,16 +38,43 @@ impl Plugin for SetupPlugin {
             })
         )
             .add_plugins((
-                RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(PIXELS_PER_METER),
+                RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(self.config.pixels_per_meter),
                 RapierDebugRenderPlugin::default(),
             ))
             .add_plugins((EguiPlugin, DefaultInspectorConfigPlugin, DebugLinesPlugin::default()))
             .add_event::<MoveCamera>()
             .init_resource::<GameControl>()
+            .init_resource::<InspectorSelection>()
             .add_systems(Startup, setup_config)
-            .add_systems(Update, (inspector_ui.run_if(input_toggle_active(false, KeyCode::I)),))
+            .add_systems(Update, apply_camera_scale.run_if(resource_changed::<SetupConfig>()))
+            .add_systems(Update, (inspector_ui.run_if(action_toggle_active(false, GameAction::ToggleInspector)),));
             // .add_systems(Update, ui_example_system)
-            .add_systems(Last, (bevy::window::close_on_esc, frame_time));
+    }
+}
+
+/// The simulation without a window, renderer or ui, stepping physics at a fixed rate
+#[derive(Default)]
+pub struct HeadlessPlugin {
+    pub config: SetupConfig,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Headless)
+            .insert_resource(self.config.clone())
+            .add_plugins((
+                MinimalPlugins,
+                bevy::log::LogPlugin::default(),
+                TransformPlugin,
+                HierarchyPlugin,
+                bevy::input::InputPlugin,
+            ))
+            .insert_resource(RapierConfiguration {
+                gravity: self.config.gravity,
+                timestep_mode: TimestepMode::Fixed { dt: self.config.fixed_dt, substeps: 1 },
+                ..Default::default()
+            })
+            .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(self.config.pixels_per_meter));
     }
 }
 
@@ -58,125 +86,121 @@ pub struct GameControl {
 #[derive(Event)]
 pub struct MoveCamera {
     pub to: Vec2,
+    /// Orthographic scale to zoom to, if any
+    pub scale: Option<f32>,
 }
 
-fn frame_time(
-    mut contexts: EguiContexts,
-    time: Res<Time>,
-    mut timer: Local<Stopwatch>,
-    mut frame_datapoints: Local<Vec<f64>>,
-    mut latest_average: Local<f64>,
-    mut datapoints: Local<Vec<[f64; 2]>>
+fn setup_config(
+    mut commands: Commands,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    config: Res<SetupConfig>
 ) {
-    timer.tick(time.delta());
-    frame_datapoints.push(time.delta_seconds_f64());
-    // per second
-
-    if timer.elapsed_secs() > 1.0 {
-        let average = frame_datapoints.iter().sum::<f64>() / (frame_datapoints.len() as f64);
-        datapoints.push([time.elapsed_seconds_f64(), 1.0 / average]);
-        *latest_average = average;
-        timer.reset();
-        frame_datapoints.clear();
-    }
-
-    egui::Window
-        ::new("Stats")
-        .default_size([100.0, 200.0])
-        .default_pos([0.0, -1000.0])
-        .default_open(true)
-        .show(contexts.ctx_mut(), |ui| {
-            let time_since_start = time.elapsed_seconds_f64();
-            let fps = 1.0 / *latest_average;
-            ui.label(format!("Second {:.*}:\tFrame time: {:.*}", 0, time_since_start, 5, fps));
-        });
-}
-
-fn setup_config(mut commands: Commands, mut rapier_config: ResMut<RapierConfiguration>) {
-    if !GRAVITY {
-        rapier_config.gravity = Vec2::ZERO;
-    } else {
-        rapier_config.gravity = Vect::Y * -9.81 * 100.0;
+    rapier_config.gravity = config.gravity;
+    if config.fixed_timestep {
+        rapier_config.timestep_mode = TimestepMode::Fixed { dt: config.fixed_dt, substeps: 1 };
     }
     // Add a camera so we can see the debug-render.
     commands.spawn(Camera2dBundle::default()).insert(OrthographicProjection {
-        scale: DEFAULT_CAM_SCALE,
+        scale: config.camera_scale,
         ..Default::default()
     });
 }
 
+/// A changed camera_scale zooms the camera to it, the starting scale is already set by setup_config
+fn apply_camera_scale(
+    config: Res<SetupConfig>,
+    mut camera: Query<&mut OrthographicProjection, With<Camera>>,
+    mut applied: Local<Option<f32>>
+) {
+    // other setup settings changing leave the zoom alone
+    let last = applied.replace(config.camera_scale);
+    if last.is_none() || last == Some(config.camera_scale) {
+        return;
+    }
+    for mut proj in camera.iter_mut() {
+        proj.scale = config.camera_scale;
+    }
+}
+
+/// What the inspector shows, shared so clicks in the world can select too
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct InspectorSelection(pub SelectedEntities);
+
 /// Sets up the inspector UI.
-fn inspector_ui(world: &mut World, mut selected_entities: Local<SelectedEntities>) {
-    let mut egui_context = world
-        .query_filtered::<&mut EguiContext, With<PrimaryWindow>>()
-        .single(world)
-        .clone();
-    egui::SidePanel
-        ::left("hierarchy")
-        .default_width(200.0)
-        .show(egui_context.get_mut(), |ui| {
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                ui.heading("Hierarchy");
-
-                let gs = world.get_resource_mut::<GUISelect>();
-
-                let mut empty = true;
-                if let Some(gs) = gs.as_ref() {
-                    for entity in gs.entities().iter() {
-                        empty = false;
-                        let name = format!("{:?}", entity);
-                        if ui.button(name).clicked() {
-                            selected_entities.select_replace(**entity);
+fn inspector_ui(world: &mut World) {
+    world.resource_scope(|world, mut selection: Mut<InspectorSelection>| {
+        let selected_entities = &mut selection.0;
+        let mut egui_context = world
+            .query_filtered::<&mut EguiContext, With<PrimaryWindow>>()
+            .single(world)
+            .clone();
+        egui::SidePanel
+            ::left("hierarchy")
+            .default_width(200.0)
+            .show(egui_context.get_mut(), |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.heading("Hierarchy");
+
+                    let gs = world.get_resource_mut::<GUISelect>();
+
+                    let mut empty = true;
+                    if let Some(gs) = gs.as_ref() {
+                        for entity in gs.entities().iter() {
+                            empty = false;
+                            let name = format!("{:?}", entity);
+                            if ui.button(name).clicked() {
+                                selected_entities.select_replace(**entity);
+                            }
                         }
                     }
-                }
-                if !empty {
-                    // clear
-                    if ui.button("Clear Selected").clicked() {
-                        if let Some(mut gs) = gs {
-                            gs.clear();
+                    if !empty {
+                        // clear
+                        if ui.button("Clear Selected").clicked() {
+                            if let Some(mut gs) = gs {
+                                gs.clear();
+                            }
                         }
                     }
-                }
-                ui.separator();
-
-                // button deselect all
-                if ui.button("Deselect All").clicked() {
-                    selected_entities.clear();
-                }
-
-                bevy_inspector_egui::bevy_inspector::hierarchy::hierarchy_ui(
-                    world,
-                    ui,
-                    &mut selected_entities
-                );
-
-                ui.label("Press I to toggle UI");
-                ui.allocate_space(ui.available_size());
-            });
-        });
-
-    egui::SidePanel
-        ::right("inspector")
-        .default_width(250.0)
-        .show(egui_context.get_mut(), |ui| {
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                ui.heading("Inspector");
-
-                match selected_entities.as_slice() {
-                    &[entity] => {
-                        bevy_inspector_egui::bevy_inspector::ui_for_entity(world, entity, ui);
+                    ui.separator();
+
+                    // button deselect all
+                    if ui.button("Deselect All").clicked() {
+                        selected_entities.clear();
                     }
-                    entities => {
-                        bevy_inspector_egui::bevy_inspector::ui_for_entities_shared_components(
-                            world,
-                            entities,
-                            ui
-                        );
+
+                    bevy_inspector_egui::bevy_inspector::hierarchy::hierarchy_ui(
+                        world,
+                        ui,
+                        selected_entities
+                    );
+
+                    ui.label("Press I to toggle UI");
+                    ui.allocate_space(ui.available_size());
+                });
+            });
+
+        egui::SidePanel
+            ::right("inspector")
+            .default_width(250.0)
+            .show(egui_context.get_mut(), |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.heading("Inspector");
+
+                    match selected_entities.as_slice() {
+                        &[entity] => {
+                            bevy_inspector_egui::bevy_inspector::ui_for_entity(world, entity, ui);
+                        }
+                        entities => {
+                            bevy_inspector_egui::bevy_inspector::ui_for_entities_shared_components(
+                                world,
+                                entities,
+                                ui
+                            );
+                        }
                     }
-                }
 
-                ui.allocate_space(ui.available_size());
+                    ui.allocate_space(ui.available_size());
+                });
             });
-        });
+    });
 }